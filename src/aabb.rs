@@ -0,0 +1,120 @@
+use linalg::Point;
+
+use crate::{Interval, Ray};
+
+/// An axis-aligned bounding box.
+#[derive(Clone, Copy, Debug)]
+pub struct Aabb {
+    pub x: Interval,
+    pub y: Interval,
+    pub z: Interval,
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl Aabb {
+    pub const fn new(x: Interval, y: Interval, z: Interval) -> Self {
+        Self { x, y, z }
+    }
+
+    pub const fn empty() -> Self {
+        Self::new(Interval::empty(), Interval::empty(), Interval::empty())
+    }
+
+    /// Returns the box with `a` and `b` as opposite corners.
+    pub fn from_points(a: Point<f64, 3>, b: Point<f64, 3>) -> Self {
+        let span = |a: f64, b: f64| Interval::new(a.min(b), a.max(b));
+        Self::new(span(a.x(), b.x()), span(a.y(), b.y()), span(a.z(), b.z()))
+    }
+
+    /// Returns the smallest box containing both `a` and `b`.
+    pub fn union(a: Aabb, b: Aabb) -> Self {
+        Self::new(
+            Interval::union(a.x, b.x),
+            Interval::union(a.y, b.y),
+            Interval::union(a.z, b.z),
+        )
+    }
+
+    pub fn axis(&self, n: usize) -> Interval {
+        match n {
+            1 => self.y,
+            2 => self.z,
+            _ => self.x,
+        }
+    }
+
+    /// Returns the index of the axis along which the box is widest.
+    pub fn longest_axis(&self) -> usize {
+        if self.x.size() > self.y.size() {
+            if self.x.size() > self.z.size() {
+                0
+            } else {
+                2
+            }
+        } else if self.y.size() > self.z.size() {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// Returns the box widened along any axis where it is too thin for rays to reliably hit,
+    /// as for flat, axis-aligned triangles.
+    pub fn padded(&self) -> Self {
+        const MINIMUM: f64 = 1e-4;
+        let pad = |interval: Interval| {
+            if interval.size() < MINIMUM {
+                interval.expand(MINIMUM)
+            } else {
+                interval
+            }
+        };
+        Self::new(pad(self.x), pad(self.y), pad(self.z))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.x.min > self.x.max || self.y.min > self.y.max || self.z.min > self.z.max
+    }
+
+    pub fn corners(&self) -> [Point<f64, 3>; 8] {
+        let mut corners = [Point::default(); 8];
+        for (n, corner) in corners.iter_mut().enumerate() {
+            *corner = Point::new([
+                if n & 1 == 0 { self.x.min } else { self.x.max },
+                if n & 2 == 0 { self.y.min } else { self.y.max },
+                if n & 4 == 0 { self.z.min } else { self.z.max },
+            ]);
+        }
+        corners
+    }
+
+    pub fn hit(&self, ray: &Ray, mut ray_t: Interval) -> bool {
+        let origin = ray.origin();
+        let direction = ray.direction();
+        let origins = [origin.x(), origin.y(), origin.z()];
+        let directions = [direction.x(), direction.y(), direction.z()];
+
+        for (n, (origin, direction)) in origins.into_iter().zip(directions).enumerate() {
+            let axis = self.axis(n);
+            let inverse = 1.0 / direction;
+
+            let t0 = (axis.min - origin) * inverse;
+            let t1 = (axis.max - origin) * inverse;
+            let (t0, t1) = if t0 < t1 { (t0, t1) } else { (t1, t0) };
+
+            ray_t.min = ray_t.min.max(t0);
+            ray_t.max = ray_t.max.min(t1);
+
+            if ray_t.max <= ray_t.min {
+                return false;
+            }
+        }
+
+        true
+    }
+}
@@ -0,0 +1,77 @@
+use std::sync::Arc;
+
+use crate::{
+    colour::{luminance, Colour},
+    hittable::{HitRecord, SurfacePoint},
+    material::{Material, ScatterSample},
+    medium::Medium,
+    texture::Texture,
+    Ray, Vector,
+};
+
+/// Cuts holes in another material's surface with an alpha texture, read as its luminance, so
+/// that flat geometry can stand in for leaves or fences. Rays pass straight through the holes.
+pub struct AlphaMask {
+    pub material: Arc<dyn Material>,
+    pub alpha: Arc<dyn Texture>,
+    /// If set, the surface is solid where alpha reaches the threshold and cut away elsewhere.
+    /// Otherwise, partial alpha is rendered by letting rays through at random.
+    pub threshold: Option<f64>,
+}
+
+impl AlphaMask {
+    /// A mask with a hard edge at an alpha of one half.
+    pub fn cutout(material: Arc<dyn Material>, alpha: Arc<dyn Texture>) -> Self {
+        Self {
+            material,
+            alpha,
+            threshold: Some(0.5),
+        }
+    }
+
+    /// A mask rendering partial alpha as partial transparency.
+    pub fn stochastic(material: Arc<dyn Material>, alpha: Arc<dyn Texture>) -> Self {
+        Self {
+            material,
+            alpha,
+            threshold: None,
+        }
+    }
+}
+
+impl Material for AlphaMask {
+    fn sample(&self, ray_in: &Ray, record: &HitRecord) -> Option<ScatterSample> {
+        self.material.sample(ray_in, record)
+    }
+
+    fn eval(&self, ray_in: &Ray, record: &HitRecord, direction: Vector<f64, 3>) -> Colour {
+        self.material.eval(ray_in, record, direction)
+    }
+
+    fn pdf(&self, ray_in: &Ray, record: &HitRecord, direction: Vector<f64, 3>) -> f64 {
+        self.material.pdf(ray_in, record, direction)
+    }
+
+    fn opacity(&self, surface: &SurfacePoint) -> f64 {
+        let alpha = luminance(self.alpha.value(surface.u, surface.v, surface.p)).clamp(0., 1.);
+        let alpha = match self.threshold {
+            Some(threshold) => {
+                if alpha >= threshold {
+                    1.
+                } else {
+                    0.
+                }
+            }
+            None => alpha,
+        };
+        alpha * self.material.opacity(surface)
+    }
+
+    fn emitted(&self, ray_in: &Ray, record: &HitRecord) -> Colour {
+        self.material.emitted(ray_in, record)
+    }
+
+    fn medium(&self) -> Option<Medium> {
+        self.material.medium()
+    }
+}
@@ -0,0 +1,691 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    colour::Colour,
+    hittable::{HitRecord, Hittable, SurfacePoint},
+    material::{Material, ScatterSample},
+    medium::Medium,
+    mix::sample_layers,
+    Interval, Ray, Vector,
+};
+
+/// How a value moves from one keyframe to the next.
+#[derive(Clone, Copy, Debug, Default)]
+pub enum Interpolation {
+    /// Holds the keyframe's value until the next one.
+    Step,
+    /// Moves at a constant rate. Rotations are slerped.
+    #[default]
+    Linear,
+    /// Eases in and out along a cubic Bézier timing curve from `(0, 0)` to `(1, 1)` with control
+    /// points `(x1, y1)` and `(x2, y2)`, as in CSS's `cubic-bezier`.
+    Bezier { x1: f64, y1: f64, x2: f64, y2: f64 },
+}
+
+impl Interpolation {
+    /// A gentle ease-in-out.
+    pub const EASE: Interpolation = Interpolation::Bezier {
+        x1: 0.42,
+        y1: 0.,
+        x2: 0.58,
+        y2: 1.,
+    };
+
+    /// Maps the fraction of time elapsed between two keyframes to the fraction of the way the
+    /// value should have moved.
+    fn ease(&self, t: f64) -> f64 {
+        match *self {
+            Interpolation::Step => 0.,
+            Interpolation::Linear => t,
+            Interpolation::Bezier { x1, y1, x2, y2 } => {
+                let bezier = |a: f64, b: f64, s: f64| {
+                    let r = 1. - s;
+                    3. * r * r * s * a + 3. * r * s * s * b + s * s * s
+                };
+
+                // x(s) is monotonic for x1, x2 in [0, 1], so bisect to find the s giving t
+                let (mut lo, mut hi) = (0., 1.);
+                for _ in 0..32 {
+                    let mid = (lo + hi) / 2.;
+                    if bezier(x1, x2, mid) < t {
+                        lo = mid;
+                    } else {
+                        hi = mid;
+                    }
+                }
+                bezier(y1, y2, (lo + hi) / 2.)
+            }
+        }
+    }
+
+    /// The smallest and largest values `ease` can return. A Bézier curve lies within the hull of
+    /// its control points, so may overshoot `[0, 1]`.
+    fn range(&self) -> (f64, f64) {
+        match *self {
+            Interpolation::Step => (0., 0.),
+            Interpolation::Linear => (0., 1.),
+            Interpolation::Bezier { y1, y2, .. } => (y1.min(y2).min(0.), y1.max(y2).max(1.)),
+        }
+    }
+}
+
+/// A value that can be blended between keyframes.
+pub trait Animatable: Copy {
+    fn blend(a: Self, b: Self, t: f64) -> Self;
+}
+
+impl Animatable for f64 {
+    fn blend(a: Self, b: Self, t: f64) -> Self {
+        a + (b - a) * t
+    }
+}
+
+impl Animatable for Vector<f64, 3> {
+    fn blend(a: Self, b: Self, t: f64) -> Self {
+        a + (b - a) * t
+    }
+}
+
+impl Animatable for Quaternion {
+    fn blend(a: Self, b: Self, t: f64) -> Self {
+        Quaternion::slerp(a, b, t)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Keyframe<T: Animatable> {
+    pub time: f64,
+    pub value: T,
+    /// How to move from this keyframe to the next.
+    pub interpolation: Interpolation,
+}
+
+impl<T: Animatable> Keyframe<T> {
+    pub fn new(time: f64, value: T, interpolation: Interpolation) -> Self {
+        Self {
+            time,
+            value,
+            interpolation,
+        }
+    }
+}
+
+/// A value animated over time by a sequence of keyframes. Before the first keyframe and after the
+/// last, the value holds still.
+#[derive(Clone, Debug)]
+pub struct Track<T: Animatable> {
+    keyframes: Vec<Keyframe<T>>,
+}
+
+impl<T: Animatable> Track<T> {
+    pub fn new(mut keyframes: Vec<Keyframe<T>>) -> Self {
+        assert!(!keyframes.is_empty(), "A track needs at least one keyframe");
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Self { keyframes }
+    }
+
+    /// A track which never changes.
+    pub fn constant(value: T) -> Self {
+        Self::new(vec![Keyframe::new(0., value, Interpolation::Step)])
+    }
+
+    pub fn keyframes(&self) -> &[Keyframe<T>] {
+        &self.keyframes
+    }
+
+    pub fn at(&self, time: f64) -> T {
+        let next = self.keyframes.partition_point(|k| k.time <= time);
+        if next == 0 {
+            return self.keyframes[0].value;
+        }
+        if next == self.keyframes.len() {
+            return self.keyframes[next - 1].value;
+        }
+
+        let a = &self.keyframes[next - 1];
+        let b = &self.keyframes[next];
+        let t = (time - a.time) / (b.time - a.time);
+        T::blend(a.value, b.value, a.interpolation.ease(t))
+    }
+
+    /// The keyframes the track moves between from `start` to `end`, which must not have a
+    /// keyframe strictly between them, or `None` if the value holds still.
+    fn span(&self, start: f64, end: f64) -> Option<(&Keyframe<T>, &Keyframe<T>)> {
+        let middle = (start + end) / 2.;
+        let next = self.keyframes.partition_point(|k| k.time <= middle);
+        if next == 0 || next == self.keyframes.len() {
+            return None;
+        }
+        Some((&self.keyframes[next - 1], &self.keyframes[next]))
+    }
+}
+
+impl Track<Vector<f64, 3>> {
+    /// A box containing every value from `start` to `end`, which must not have a keyframe
+    /// strictly between them.
+    fn bounds(&self, start: f64, end: f64) -> Aabb {
+        let mut bbox = Aabb::from_points(self.at(start), self.at(end));
+        if let Some((a, b)) = self.span(start, end) {
+            let (lo, hi) = a.interpolation.range();
+            let blend = |t: f64| a.value + (b.value - a.value) * t;
+            bbox = Aabb::union(bbox, Aabb::from_points(blend(lo), blend(hi)));
+        }
+        bbox
+    }
+}
+
+impl Track<Quaternion> {
+    /// Whether the rotation turns at all from `start` to `end`, which must not have a keyframe
+    /// strictly between them.
+    fn turns(&self, start: f64, end: f64) -> bool {
+        match self.span(start, end) {
+            Some((a, b)) => {
+                !matches!(a.interpolation, Interpolation::Step)
+                    && a.value.dot(&b.value).abs() < 1. - 1e-12
+            }
+            None => false,
+        }
+    }
+}
+
+/// A unit quaternion, representing a rotation.
+#[derive(Clone, Copy, Debug)]
+pub struct Quaternion {
+    pub w: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Default for Quaternion {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Quaternion {
+    pub const fn identity() -> Self {
+        Self {
+            w: 1.,
+            x: 0.,
+            y: 0.,
+            z: 0.,
+        }
+    }
+
+    /// A rotation of `degrees` about `axis`, anticlockwise when looking down the axis.
+    pub fn from_axis_angle(axis: Vector<f64, 3>, degrees: f64) -> Self {
+        let axis = axis.unit();
+        let half = degrees.to_radians() / 2.;
+        let s = half.sin();
+        Self {
+            w: half.cos(),
+            x: axis.x() * s,
+            y: axis.y() * s,
+            z: axis.z() * s,
+        }
+    }
+
+    pub fn conjugate(&self) -> Self {
+        Self {
+            w: self.w,
+            x: -self.x,
+            y: -self.y,
+            z: -self.z,
+        }
+    }
+
+    fn dot(&self, other: &Self) -> f64 {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    fn normalised(&self) -> Self {
+        let length = self.dot(self).sqrt();
+        Self {
+            w: self.w / length,
+            x: self.x / length,
+            y: self.y / length,
+            z: self.z / length,
+        }
+    }
+
+    pub fn rotate(&self, v: Vector<f64, 3>) -> Vector<f64, 3> {
+        // v' = v + 2w(q x v) + 2q x (q x v), where q is the vector part
+        let q = Vector::new([self.x, self.y, self.z]);
+        let t = q.cross(v) * 2.;
+        v + t * self.w + q.cross(t)
+    }
+
+    /// Spherically interpolates between two rotations, taking the shorter way round.
+    pub fn slerp(a: Self, b: Self, t: f64) -> Self {
+        let mut b = b;
+        let mut cos_theta = a.dot(&b);
+        if cos_theta < 0. {
+            b = Self {
+                w: -b.w,
+                x: -b.x,
+                y: -b.y,
+                z: -b.z,
+            };
+            cos_theta = -cos_theta;
+        }
+
+        // Nearly parallel, so a linear blend is accurate and avoids dividing by ~0
+        let (wa, wb) = if cos_theta > 0.9995 {
+            (1. - t, t)
+        } else {
+            let theta = cos_theta.acos();
+            let sin_theta = theta.sin();
+            (
+                ((1. - t) * theta).sin() / sin_theta,
+                (t * theta).sin() / sin_theta,
+            )
+        };
+
+        Self {
+            w: a.w * wa + b.w * wb,
+            x: a.x * wa + b.x * wb,
+            y: a.y * wa + b.y * wb,
+            z: a.z * wa + b.z * wb,
+        }
+        .normalised()
+    }
+}
+
+/// An animated scale, then rotation, then translation.
+#[derive(Clone, Debug)]
+pub struct Transform {
+    pub translation: Track<Vector<f64, 3>>,
+    pub rotation: Track<Quaternion>,
+    pub scale: Track<Vector<f64, 3>>,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            translation: Track::constant(Vector::new([0., 0., 0.])),
+            rotation: Track::constant(Quaternion::identity()),
+            scale: Track::constant(Vector::new([1., 1., 1.])),
+        }
+    }
+}
+
+impl Transform {
+    /// Every time at which the transform's motion changes character.
+    fn key_times(&self) -> Vec<f64> {
+        let mut times: Vec<f64> = self
+            .translation
+            .keyframes()
+            .iter()
+            .map(|k| k.time)
+            .chain(self.rotation.keyframes().iter().map(|k| k.time))
+            .chain(self.scale.keyframes().iter().map(|k| k.time))
+            .collect();
+        times.sort_by(f64::total_cmp);
+        times.dedup();
+        times
+    }
+}
+
+/// Wraps any hittable, moving it according to an animated transform over ray time.
+pub struct Animated<H: Hittable + ?Sized> {
+    object: Arc<H>,
+    transform: Transform,
+    bbox: Aabb,
+}
+
+impl<H: Hittable + ?Sized> Animated<H> {
+    pub fn new(object: Arc<H>, transform: Transform) -> Self {
+        let mut animated = Self {
+            object,
+            transform,
+            bbox: Aabb::empty(),
+        };
+        animated.bbox = animated.motion_bounds();
+        animated
+    }
+
+    /// Bounds the object over its whole motion. Between each pair of keyframe times, the range
+    /// of each track is bounded exactly, and a turning rotation is bounded by the sphere the
+    /// scaled box sweeps out, so the result always encloses the object.
+    fn motion_bounds(&self) -> Aabb {
+        let object_box = self.object.bounding_box();
+        let times = self.transform.key_times();
+
+        let mut spans: Vec<(f64, f64)> = times.windows(2).map(|w| (w[0], w[1])).collect();
+        spans.push((times[0], times[0]));
+
+        let mut bbox = Aabb::empty();
+        for (start, end) in spans {
+            // Scaling each axis by a range of factors
+            let scale = self.transform.scale.bounds(start, end);
+            let product = |a: Interval, b: Interval| {
+                let products = [a.min * b.min, a.min * b.max, a.max * b.min, a.max * b.max];
+                Interval::new(
+                    products.into_iter().fold(f64::INFINITY, f64::min),
+                    products.into_iter().fold(f64::NEG_INFINITY, f64::max),
+                )
+            };
+            let scaled = Aabb::new(
+                product(object_box.x, scale.x),
+                product(object_box.y, scale.y),
+                product(object_box.z, scale.z),
+            );
+
+            let rotation = &self.transform.rotation;
+            let rotated = if rotation.turns(start, end) {
+                let radius = scaled
+                    .corners()
+                    .iter()
+                    .map(|c| c.length())
+                    .fold(0., f64::max);
+                let r = Vector::new([radius, radius, radius]);
+                Aabb::from_points(-r, r)
+            } else {
+                let mut rotated = Aabb::empty();
+                for time in [start, end] {
+                    for corner in scaled.corners() {
+                        let p = rotation.at(time).rotate(corner);
+                        rotated = Aabb::union(rotated, Aabb::from_points(p, p));
+                    }
+                }
+                rotated
+            };
+
+            let translation = self.transform.translation.bounds(start, end);
+            let sum = |a: Interval, b: Interval| Interval::new(a.min + b.min, a.max + b.max);
+            bbox = Aabb::union(
+                bbox,
+                Aabb::new(
+                    sum(rotated.x, translation.x),
+                    sum(rotated.y, translation.y),
+                    sum(rotated.z, translation.z),
+                ),
+            );
+        }
+        bbox
+    }
+}
+
+impl<H: Hittable + ?Sized> Hittable for Animated<H> {
+    fn hit(&self, ray: &Ray, ray_t: Interval, record: &mut HitRecord) -> bool {
+        let time = ray.time();
+        let translation = self.transform.translation.at(time);
+        let rotation = self.transform.rotation.at(time);
+        let scale = self.transform.scale.at(time);
+        let inverse_scale = Vector::new([1. / scale.x(), 1. / scale.y(), 1. / scale.z()]);
+
+        // Move the ray into object space. The transform is affine, so distances along the ray
+        // are the same in both spaces.
+        let to_object = |v: Vector<f64, 3>| rotation.conjugate().rotate(v).hadamard(inverse_scale);
+        let origin = to_object(ray.origin() - translation);
+        let direction = to_object(ray.direction());
+        let object_ray = Ray::new(origin, direction, Some(time));
+
+        if !self.object.hit(&object_ray, ray_t, record) {
+            return false;
+        }
+
+        // Move the hit back into world space. Normals transform by the inverse transpose, which
+        // for a rotation and scale is the rotation and inverse scale.
+        record.p = rotation.rotate(record.p.hadamard(scale)) + translation;
+        record.normal = rotation
+            .rotate(record.normal.hadamard(inverse_scale))
+            .unit();
+        // Tangents move with the surface
+        record.dpdu = rotation.rotate(record.dpdu.hadamard(scale));
+        record.dpdv = rotation.rotate(record.dpdv.hadamard(scale));
+
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
+
+/// Blends from one material to another over ray time, picking one at random at each hit as
+/// `MixMaterial` does over a surface. Where `weight` is 0 the surface is entirely `a`, and
+/// where it is 1 entirely `b`.
+pub struct AnimatedMaterial {
+    pub a: Arc<dyn Material>,
+    pub b: Arc<dyn Material>,
+    pub weight: Track<f64>,
+}
+
+impl AnimatedMaterial {
+    pub fn new(a: Arc<dyn Material>, b: Arc<dyn Material>, weight: Track<f64>) -> Self {
+        Self { a, b, weight }
+    }
+
+    fn weight(&self, time: f64) -> f64 {
+        self.weight.at(time).clamp(0., 1.)
+    }
+}
+
+impl Material for AnimatedMaterial {
+    fn sample(&self, ray_in: &Ray, record: &HitRecord) -> Option<ScatterSample> {
+        let weight = self.weight(ray_in.time());
+        sample_layers(
+            self,
+            [&*self.a, &*self.b],
+            [1. - weight, weight],
+            ray_in,
+            record,
+        )
+    }
+
+    fn eval(&self, ray_in: &Ray, record: &HitRecord, direction: Vector<f64, 3>) -> Colour {
+        let weight = self.weight(ray_in.time());
+        self.a.eval(ray_in, record, direction) * (1. - weight)
+            + self.b.eval(ray_in, record, direction) * weight
+    }
+
+    fn pdf(&self, ray_in: &Ray, record: &HitRecord, direction: Vector<f64, 3>) -> f64 {
+        let weight = self.weight(ray_in.time());
+        self.a.pdf(ray_in, record, direction) * (1. - weight)
+            + self.b.pdf(ray_in, record, direction) * weight
+    }
+
+    fn opacity(&self, surface: &SurfacePoint) -> f64 {
+        let weight = self.weight(surface.time);
+        self.a.opacity(surface) * (1. - weight) + self.b.opacity(surface) * weight
+    }
+
+    fn emitted(&self, ray_in: &Ray, record: &HitRecord) -> Colour {
+        let weight = self.weight(ray_in.time());
+        self.a.emitted(ray_in, record) * (1. - weight) + self.b.emitted(ray_in, record) * weight
+    }
+
+    /// The interior can't change over time, so is whichever of the two materials has one.
+    fn medium(&self) -> Option<Medium> {
+        self.a.medium().or_else(|| self.b.medium())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lambertian::Lambertian, principled::Principled, sphere::Sphere};
+    use linalg::Point;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    fn track(interpolation: Interpolation) -> Track<f64> {
+        Track::new(vec![
+            Keyframe::new(1., 10., interpolation),
+            Keyframe::new(3., 20., interpolation),
+        ])
+    }
+
+    #[test]
+    fn tracks_hold_still_outside_their_keyframes() {
+        for interpolation in [
+            Interpolation::Step,
+            Interpolation::Linear,
+            Interpolation::EASE,
+        ] {
+            let track = track(interpolation);
+            assert_eq!(track.at(-5.), 10.);
+            assert_eq!(track.at(1.), 10.);
+            assert_eq!(track.at(3.), 20.);
+            assert_eq!(track.at(100.), 20.);
+        }
+    }
+
+    #[test]
+    fn steps_hold_until_the_next_keyframe() {
+        let track = track(Interpolation::Step);
+        assert_eq!(track.at(2.), 10.);
+        assert_eq!(track.at(2.999), 10.);
+    }
+
+    #[test]
+    fn linear_tracks_move_at_a_constant_rate() {
+        let track = track(Interpolation::Linear);
+        assert!(close(track.at(1.5), 12.5));
+        assert!(close(track.at(2.), 15.));
+    }
+
+    #[test]
+    fn ease_is_symmetric_and_meets_its_end_points() {
+        let track = track(Interpolation::EASE);
+        assert!((track.at(2.) - 15.).abs() < 1e-6);
+        assert!(track.at(1.2) < 11. && track.at(2.8) > 19.);
+
+        let ease = Interpolation::EASE;
+        assert!(ease.ease(0.).abs() < 1e-6);
+        assert!((ease.ease(1.) - 1.).abs() < 1e-6);
+        for t in [0.1, 0.3, 0.45] {
+            assert!((ease.ease(t) + ease.ease(1. - t) - 1.).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn keyframes_are_sorted_by_time() {
+        let track = Track::new(vec![
+            Keyframe::new(2., 1., Interpolation::Linear),
+            Keyframe::new(0., 0., Interpolation::Linear),
+        ]);
+        assert!(close(track.at(1.), 0.5));
+    }
+
+    fn is_unit(q: Quaternion) -> bool {
+        close(q.dot(&q), 1.)
+    }
+
+    #[test]
+    fn slerp_stays_unit_length() {
+        let a = Quaternion::from_axis_angle(Vector::new([1., 2., 3.]), 30.);
+        let b = Quaternion::from_axis_angle(Vector::new([-1., 0., 1.]), 150.);
+        for i in 0..=10 {
+            assert!(is_unit(Quaternion::slerp(a, b, i as f64 / 10.)));
+        }
+    }
+
+    #[test]
+    fn slerp_takes_the_shortest_path() {
+        let axis = Vector::new([0., 0., 1.]);
+        let a = Quaternion::from_axis_angle(axis, 10.);
+        let b = Quaternion::from_axis_angle(axis, 50.);
+        let negated_b = Quaternion {
+            w: -b.w,
+            x: -b.x,
+            y: -b.y,
+            z: -b.z,
+        };
+
+        // q and -q are the same rotation, so both should pass through 30 degrees halfway
+        let halfway = Quaternion::from_axis_angle(axis, 30.);
+        for b in [b, negated_b] {
+            let q = Quaternion::slerp(a, b, 0.5);
+            assert!(close(q.dot(&halfway).abs(), 1.));
+        }
+
+        let x = Vector::new([1., 0., 0.]);
+        let rotated = Quaternion::slerp(a, negated_b, 0.5).rotate(x);
+        assert!(close(rotated.x(), 30f64.to_radians().cos()));
+        assert!(close(rotated.y(), 30f64.to_radians().sin()));
+    }
+
+    fn transform() -> Transform {
+        Transform {
+            translation: Track::new(vec![
+                Keyframe::new(0., Vector::new([0., 0., 0.]), Interpolation::EASE),
+                Keyframe::new(0.5, Vector::new([2., 1., 0.]), Interpolation::Linear),
+                Keyframe::new(1., Vector::new([-1., 0., 3.]), Interpolation::Linear),
+            ]),
+            rotation: Track::new(vec![
+                Keyframe::new(0., Quaternion::identity(), Interpolation::Linear),
+                Keyframe::new(
+                    1.,
+                    Quaternion::from_axis_angle(Vector::new([0., 1., 0.]), 120.),
+                    Interpolation::Linear,
+                ),
+            ]),
+            scale: Track::new(vec![
+                Keyframe::new(0.25, Vector::new([1., 1., 1.]), Interpolation::Step),
+                Keyframe::new(0.75, Vector::new([2., 0.5, 1.]), Interpolation::Step),
+            ]),
+        }
+    }
+
+    #[test]
+    fn motion_bounds_enclose_every_keyframe() {
+        let sphere = Sphere::new(
+            Point::new([1., 0., 0.]),
+            None,
+            0.5,
+            Arc::new(Lambertian::default()),
+        );
+        let object_box = sphere.bounding_box();
+        let animated = Animated::new(Arc::new(sphere), transform());
+        let bbox = animated.bounding_box();
+
+        let inside = |p: Vector<f64, 3>| {
+            bbox.x.expand(1e-9).contains(p.x())
+                && bbox.y.expand(1e-9).contains(p.y())
+                && bbox.z.expand(1e-9).contains(p.z())
+        };
+        let transform = transform();
+        let times = transform.key_times();
+        assert_eq!(times, [0., 0.25, 0.5, 0.75, 1.]);
+        for time in times {
+            for corner in object_box.corners() {
+                let p = transform
+                    .rotation
+                    .at(time)
+                    .rotate(corner.hadamard(transform.scale.at(time)))
+                    + transform.translation.at(time);
+                assert!(inside(p), "{p:?} at {time}");
+            }
+        }
+    }
+
+    #[test]
+    fn animated_materials_blend_over_time() {
+        let glowing = Principled {
+            emission: Colour::new([1., 1., 1.]),
+            ..Default::default()
+        };
+        let weight = Track::new(vec![
+            Keyframe::new(0., 0., Interpolation::Linear),
+            Keyframe::new(1., 1., Interpolation::Linear),
+        ]);
+        let material =
+            AnimatedMaterial::new(Arc::new(glowing), Arc::new(Principled::default()), weight);
+
+        let record = HitRecord::default();
+        for (time, expected) in [(-1., 1.), (0.25, 0.75), (0.5, 0.5), (2., 0.)] {
+            let ray = Ray::new(
+                Point::new([0., 0., 1.]),
+                Vector::new([0., 0., -1.]),
+                Some(time),
+            );
+            assert!(close(material.emitted(&ray, &record).x(), expected));
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use std::{fs::File, io, ops::Add};
+
+use crate::{
+    colour::Colour,
+    exr::{write_exr, write_pfm, Channel},
+    image::Image,
+    Vector,
+};
+
+/// A single sample's colour, along with what the camera ray saw at its first hit.
+#[derive(Clone, Copy, Default)]
+pub struct AovSample {
+    pub colour: Colour,
+    /// Light reaching the first hit straight from a light source, or the background itself.
+    pub direct: Colour,
+    /// Light reaching the first hit after bouncing off other surfaces.
+    pub indirect: Colour,
+    pub albedo: Colour,
+    pub normal: Vector<f64, 3>,
+    /// Distance from the camera to the first hit, summed only over samples which hit something.
+    pub depth: f64,
+    /// How many samples hit something, to average `depth` over.
+    pub hits: u32,
+    pub object_id: u32,
+    pub material_id: u32,
+}
+
+impl Add for AovSample {
+    type Output = Self;
+
+    /// Sums two samples. IDs can't be summed, so a pixel keeps the first non-zero ID it sees.
+    fn add(self, rhs: Self) -> Self {
+        Self {
+            colour: self.colour + rhs.colour,
+            direct: self.direct + rhs.direct,
+            indirect: self.indirect + rhs.indirect,
+            albedo: self.albedo + rhs.albedo,
+            normal: self.normal + rhs.normal,
+            depth: self.depth + rhs.depth,
+            hits: self.hits + rhs.hits,
+            object_id: if self.object_id != 0 {
+                self.object_id
+            } else {
+                rhs.object_id
+            },
+            material_id: if self.material_id != 0 {
+                self.material_id
+            } else {
+                rhs.material_id
+            },
+        }
+    }
+}
+
+/// The buffers produced by a render, each holding values summed over every sample of a pixel,
+/// except for depth, which is averaged over the samples which hit something and is infinite
+/// where none did. Scalar layers (depth and IDs) store their value in every channel.
+#[derive(Clone, Default)]
+pub struct Layers {
+    pub beauty: Image,
+    pub direct: Image,
+    pub indirect: Image,
+    pub albedo: Image,
+    pub normal: Image,
+    pub depth: Image,
+    pub object_id: Image,
+    pub material_id: Image,
+}
+
+/// Where to write a render's layers.
+#[derive(Clone, Debug)]
+pub enum AovOutput {
+    /// One Portable Float Map per layer, named `<prefix>_<layer>.pfm`.
+    Files { prefix: String },
+    /// A single multi-channel OpenEXR file.
+    Exr { path: String },
+}
+
+impl Layers {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            beauty: Image::new(width, height),
+            direct: Image::new(width, height),
+            indirect: Image::new(width, height),
+            albedo: Image::new(width, height),
+            normal: Image::new(width, height),
+            depth: Image::new(width, height),
+            object_id: Image::new(width, height),
+            material_id: Image::new(width, height),
+        }
+    }
+
+    pub fn set(&mut self, i: u32, j: u32, sample: AovSample) {
+        let scalar = |x: f64| Colour::new([x, x, x]);
+
+        self.beauty.set(i, j, sample.colour);
+        self.direct.set(i, j, sample.direct);
+        self.indirect.set(i, j, sample.indirect);
+        self.albedo.set(i, j, sample.albedo);
+        self.normal.set(i, j, sample.normal);
+        let depth = if sample.hits > 0 {
+            sample.depth / sample.hits as f64
+        } else {
+            f64::INFINITY
+        };
+        self.depth.set(i, j, scalar(depth));
+        self.object_id.set(i, j, scalar(sample.object_id as f64));
+        self.material_id
+            .set(i, j, scalar(sample.material_id as f64));
+    }
+
+    pub fn images(&self) -> [&Image; 8] {
+        [
+            &self.beauty,
+            &self.direct,
+            &self.indirect,
+            &self.albedo,
+            &self.normal,
+            &self.depth,
+            &self.object_id,
+            &self.material_id,
+        ]
+    }
+
+    pub fn images_mut(&mut self) -> [&mut Image; 8] {
+        [
+            &mut self.beauty,
+            &mut self.direct,
+            &mut self.indirect,
+            &mut self.albedo,
+            &mut self.normal,
+            &mut self.depth,
+            &mut self.object_id,
+            &mut self.material_id,
+        ]
+    }
+
+    /// Copies every layer of `source` into this one, with its top left corner at `(x, y)`.
+    pub fn blit(&mut self, x: u32, y: u32, source: &Layers) {
+        for (image, source) in self.images_mut().into_iter().zip(source.images()) {
+            image.blit(x, y, source);
+        }
+    }
+
+    /// Copies every layer of `rows` into this one, starting at row `start`.
+    pub fn blit_rows(&mut self, start: u32, rows: &Layers) {
+        for (image, rows) in self.images_mut().into_iter().zip(rows.images()) {
+            image.blit_rows(start, rows);
+        }
+    }
+
+    /// Returns these layers with every summed value multiplied by `factor`, e.g. to average the
+    /// samples. Depth, which is already averaged, and IDs are left alone.
+    pub fn scaled(&self, factor: f64) -> Layers {
+        let mut layers = self.clone();
+        for image in [
+            &mut layers.beauty,
+            &mut layers.direct,
+            &mut layers.indirect,
+            &mut layers.albedo,
+            &mut layers.normal,
+        ] {
+            image.scale(factor);
+        }
+        layers
+    }
+
+    pub fn write(&self, output: &AovOutput) -> io::Result<()> {
+        let (width, height) = (self.beauty.width, self.beauty.height);
+
+        let colour = |image: &Image, prefix: &str, names: [&str; 3]| {
+            let channel = |name: &str, component: fn(&Colour) -> f64| {
+                let samples = image.pixels.iter().map(|p| component(p) as f32).collect();
+                Channel::new(&format!("{prefix}{name}"), samples)
+            };
+            vec![
+                channel(names[0], Colour::x),
+                channel(names[1], Colour::y),
+                channel(names[2], Colour::z),
+            ]
+        };
+        let scalar = |image: &Image, name: &str| {
+            let samples = image.pixels.iter().map(|p| p.x() as f32).collect();
+            vec![Channel::new(name, samples)]
+        };
+
+        const RGB: [&str; 3] = ["R", "G", "B"];
+        const XYZ: [&str; 3] = ["X", "Y", "Z"];
+
+        match output {
+            AovOutput::Files { prefix } => {
+                let layers = [
+                    ("beauty", colour(&self.beauty, "", RGB)),
+                    ("direct", colour(&self.direct, "", RGB)),
+                    ("indirect", colour(&self.indirect, "", RGB)),
+                    ("albedo", colour(&self.albedo, "", RGB)),
+                    ("normal", colour(&self.normal, "", XYZ)),
+                    ("depth", scalar(&self.depth, "Z")),
+                    ("object_id", scalar(&self.object_id, "ID")),
+                    ("material_id", scalar(&self.material_id, "ID")),
+                ];
+                for (name, channels) in layers {
+                    let file = File::create(format!("{prefix}_{name}.pfm"))?;
+                    write_pfm(file, width, height, &channels)?;
+                }
+                Ok(())
+            }
+            AovOutput::Exr { path } => {
+                let channels = [
+                    colour(&self.beauty, "", RGB),
+                    colour(&self.direct, "direct.", RGB),
+                    colour(&self.indirect, "indirect.", RGB),
+                    colour(&self.albedo, "albedo.", RGB),
+                    colour(&self.normal, "N.", XYZ),
+                    scalar(&self.depth, "Z"),
+                    scalar(&self.object_id, "objectId"),
+                    scalar(&self.material_id, "materialId"),
+                ]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<Channel>>();
+                write_exr(File::create(path)?, width, height, &channels)
+            }
+        }
+    }
+}
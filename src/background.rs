@@ -0,0 +1,71 @@
+use std::f64::consts::PI;
+
+use crate::{colour::Colour, Vector};
+
+/// What a ray sees when it escapes the scene. Backgrounds light the scene too, and those which
+/// can be importance sampled let diffuse surfaces aim rays at their brightest parts.
+pub trait Background: Send + Sync {
+    fn colour(&self, direction: Vector<f64, 3>) -> Colour;
+
+    /// Returns a random direction, chosen in proportion to how much light arrives from it.
+    fn sample_direction(&self) -> Option<Vector<f64, 3>> {
+        None
+    }
+
+    /// The probability density, per unit solid angle, of `sample_direction` returning
+    /// `direction`.
+    fn pdf(&self, _direction: Vector<f64, 3>) -> f64 {
+        0.0
+    }
+}
+
+/// A vertical blend between two colours.
+#[derive(Clone, Copy, Debug)]
+pub struct Gradient {
+    pub top: Colour,
+    pub bottom: Colour,
+}
+
+impl Gradient {
+    pub fn new(top: Colour, bottom: Colour) -> Self {
+        Self { top, bottom }
+    }
+}
+
+impl Default for Gradient {
+    /// A pale blue sky.
+    fn default() -> Self {
+        Self::new(Colour::new([0.5, 0.7, 1.0]), Colour::new([1.0, 1.0, 1.0]))
+    }
+}
+
+impl Background for Gradient {
+    fn colour(&self, direction: Vector<f64, 3>) -> Colour {
+        let unit_direction = direction.unit();
+        let a = (unit_direction.y() + 1.0) * 0.5;
+
+        self.bottom * (1.0 - a) + self.top * a
+    }
+}
+
+/// Converts a unit direction to spherical coordinates `(phi, theta)`, where `theta` is measured
+/// down from +y and `phi` anticlockwise (seen from above) around from -z.
+pub fn direction_to_spherical(direction: Vector<f64, 3>) -> (f64, f64) {
+    let theta = direction.y().clamp(-1., 1.).acos();
+    let phi = direction.x().atan2(-direction.z());
+    (phi, theta)
+}
+
+/// The inverse of `direction_to_spherical`.
+pub fn spherical_to_direction(phi: f64, theta: f64) -> Vector<f64, 3> {
+    Vector::new([
+        theta.sin() * phi.sin(),
+        theta.cos(),
+        -theta.sin() * phi.cos(),
+    ])
+}
+
+/// Wraps an angle into `[0, 2π)`.
+pub fn wrap_angle(phi: f64) -> f64 {
+    phi.rem_euclid(2. * PI)
+}
@@ -0,0 +1,217 @@
+use std::sync::Arc;
+
+use crate::{
+    colour::{linear_to_srgb, luminance, Colour},
+    hittable::{HitRecord, SurfacePoint},
+    material::{Material, ScatterSample},
+    medium::Medium,
+    texture::Texture,
+    Ray, Vector,
+};
+
+/// Perturbs the shading normal of another material with a tangent-space normal map, whose red,
+/// green and blue give the normal along the tangent, bitangent and surface normal.
+pub struct NormalMap {
+    pub material: Arc<dyn Material>,
+    /// An ordinary colour texture. Maps are stored as sRGB images, so values are re-encoded to
+    /// sRGB before being read as directions.
+    pub map: Arc<dyn Texture>,
+    /// Scales the tilt of the normals, where 1 follows the map exactly.
+    pub strength: f64,
+}
+
+impl NormalMap {
+    pub fn new(material: Arc<dyn Material>, map: Arc<dyn Texture>) -> Self {
+        Self {
+            material,
+            map,
+            strength: 1.,
+        }
+    }
+
+    fn shade(&self, record: &HitRecord) -> HitRecord {
+        let encoded = self.map.value(record.u, record.v, record.p);
+        let decode = |c: f64| 2. * linear_to_srgb(c) - 1.;
+        let local = Vector::new([
+            decode(encoded.x()) * self.strength,
+            decode(encoded.y()) * self.strength,
+            decode(encoded.z()).max(1e-3),
+        ]);
+
+        let tangent = record.tangent();
+        let mut bitangent = record.normal.cross(tangent);
+        if !record.front_face {
+            // The normal was flipped to face the ray, so flip the frame with it
+            bitangent = -bitangent;
+        }
+        let normal =
+            (tangent * local.x() + bitangent * local.y() + record.normal * local.z()).unit();
+        with_normal(record, normal)
+    }
+}
+
+impl Material for NormalMap {
+    fn sample(&self, ray_in: &Ray, record: &HitRecord) -> Option<ScatterSample> {
+        self.material.sample(ray_in, &self.shade(record))
+    }
+
+    fn eval(&self, ray_in: &Ray, record: &HitRecord, direction: Vector<f64, 3>) -> Colour {
+        self.material.eval(ray_in, &self.shade(record), direction)
+    }
+
+    fn pdf(&self, ray_in: &Ray, record: &HitRecord, direction: Vector<f64, 3>) -> f64 {
+        self.material.pdf(ray_in, &self.shade(record), direction)
+    }
+
+    fn opacity(&self, surface: &SurfacePoint) -> f64 {
+        self.material.opacity(surface)
+    }
+
+    fn emitted(&self, ray_in: &Ray, record: &HitRecord) -> Colour {
+        self.material.emitted(ray_in, &self.shade(record))
+    }
+
+    fn medium(&self) -> Option<Medium> {
+        self.material.medium()
+    }
+}
+
+/// Perturbs the shading normal of another material as if its surface were raised by a height
+/// map, read as the luminance of a texture.
+pub struct BumpMap {
+    pub material: Arc<dyn Material>,
+    pub height: Arc<dyn Texture>,
+    /// The height, in scene units, of a texture value of 1.
+    pub scale: f64,
+}
+
+impl BumpMap {
+    /// The step in `u` and `v` used to find the slope of the height map.
+    const STEP: f64 = 1e-4;
+
+    pub fn new(material: Arc<dyn Material>, height: Arc<dyn Texture>, scale: f64) -> Self {
+        Self {
+            material,
+            height,
+            scale,
+        }
+    }
+
+    fn shade(&self, record: &HitRecord) -> HitRecord {
+        let height = |u: f64, v: f64| {
+            let p = record.p + record.dpdu * (u - record.u) + record.dpdv * (v - record.v);
+            luminance(self.height.value(u, v, p)) * self.scale
+        };
+        let h = height(record.u, record.v);
+        let dhdu = (height(record.u + Self::STEP, record.v) - h) / Self::STEP;
+        let dhdv = (height(record.u, record.v + Self::STEP) - h) / Self::STEP;
+
+        // Displace the tangents by the slope, and take the normal of the raised surface
+        let dpdu = record.dpdu + record.normal * dhdu;
+        let dpdv = record.dpdv + record.normal * dhdv;
+        let mut normal = dpdu.cross(dpdv);
+        if normal.near_zero() {
+            return record.clone();
+        }
+        normal = normal.unit();
+        if normal.dot(&record.normal) < 0. {
+            normal = -normal;
+        }
+        with_normal(record, normal)
+    }
+}
+
+impl Material for BumpMap {
+    fn sample(&self, ray_in: &Ray, record: &HitRecord) -> Option<ScatterSample> {
+        self.material.sample(ray_in, &self.shade(record))
+    }
+
+    fn eval(&self, ray_in: &Ray, record: &HitRecord, direction: Vector<f64, 3>) -> Colour {
+        self.material.eval(ray_in, &self.shade(record), direction)
+    }
+
+    fn pdf(&self, ray_in: &Ray, record: &HitRecord, direction: Vector<f64, 3>) -> f64 {
+        self.material.pdf(ray_in, &self.shade(record), direction)
+    }
+
+    fn opacity(&self, surface: &SurfacePoint) -> f64 {
+        self.material.opacity(surface)
+    }
+
+    fn emitted(&self, ray_in: &Ray, record: &HitRecord) -> Colour {
+        self.material.emitted(ray_in, &self.shade(record))
+    }
+
+    fn medium(&self) -> Option<Medium> {
+        self.material.medium()
+    }
+}
+
+/// A copy of `record` with its shading normal replaced.
+fn with_normal(record: &HitRecord, normal: Vector<f64, 3>) -> HitRecord {
+    let mut shaded = record.clone();
+    shaded.normal = normal;
+    shaded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{colour::srgb_to_linear, lambertian::Lambertian, texture::SolidColour};
+    use linalg::Point;
+
+    fn record(front_face: bool) -> HitRecord {
+        let normal = Vector::new([0., 0., 1.]);
+        HitRecord {
+            normal: if front_face { normal } else { -normal },
+            front_face,
+            u: 0.3,
+            v: 0.6,
+            dpdu: Vector::new([1., 0., 0.]),
+            dpdv: Vector::new([0., 1., 0.]),
+            ..Default::default()
+        }
+    }
+
+    fn close(a: Vector<f64, 3>, b: Vector<f64, 3>) -> bool {
+        (a - b).length() < 1e-9
+    }
+
+    #[test]
+    fn flat_normal_maps_leave_the_normal_alone() {
+        let half = srgb_to_linear(0.5);
+        let map = Arc::new(SolidColour::new(Colour::new([half, half, 1.])));
+        let normal_map = NormalMap::new(Arc::new(Lambertian::default()), map);
+        for front_face in [true, false] {
+            let record = record(front_face);
+            assert!(close(normal_map.shade(&record).normal, record.normal));
+        }
+    }
+
+    #[test]
+    fn constant_heights_leave_the_normal_alone() {
+        let height = Arc::new(SolidColour::constant(0.7));
+        let bump = BumpMap::new(Arc::new(Lambertian::default()), height, 2.);
+        for front_face in [true, false] {
+            let record = record(front_face);
+            assert!(close(bump.shade(&record).normal, record.normal));
+        }
+    }
+
+    /// Rises by one unit of height per unit of `u`.
+    struct Ramp;
+
+    impl Texture for Ramp {
+        fn value(&self, u: f64, _v: f64, _p: Point<f64, 3>) -> Colour {
+            Colour::new([u, u, u])
+        }
+    }
+
+    #[test]
+    fn slopes_tilt_the_normal_away_from_the_rise() {
+        let bump = BumpMap::new(Arc::new(Lambertian::default()), Arc::new(Ramp), 1.);
+        let normal = bump.shade(&record(true)).normal;
+        let expected = Vector::new([-1., 0., 1.]).unit();
+        assert!((normal - expected).length() < 1e-6, "{normal:?}");
+    }
+}
@@ -0,0 +1,108 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable},
+    Interval, Ray,
+};
+
+/// A bounding volume hierarchy, which skips whole groups of objects a ray can't hit.
+///
+/// Boxes enclose each object's motion over all time, so one hierarchy stays valid for every
+/// frame of an animation and only needs building once.
+#[derive(Clone)]
+pub struct BvhNode {
+    left: BvhChild,
+    right: BvhChild,
+    bbox: Aabb,
+}
+
+#[derive(Clone)]
+enum BvhChild {
+    Node(Arc<BvhNode>),
+    /// An object, along with its ID (its index, starting from 1, in the original list).
+    Leaf(Arc<dyn Hittable>, u32),
+}
+
+impl BvhNode {
+    pub fn new(objects: Vec<Arc<dyn Hittable>>) -> Self {
+        assert!(!objects.is_empty(), "Can't build a BVH with no objects");
+
+        let mut leaves: Vec<BvhChild> = objects
+            .into_iter()
+            .enumerate()
+            .map(|(index, object)| BvhChild::Leaf(object, index as u32 + 1))
+            .collect();
+        Self::build(&mut leaves)
+    }
+
+    fn build(objects: &mut [BvhChild]) -> Self {
+        let bbox = objects.iter().fold(Aabb::empty(), |bbox, object| {
+            Aabb::union(bbox, object.bounding_box())
+        });
+
+        let (left, right) = match objects.len() {
+            1 => (objects[0].clone(), objects[0].clone()),
+            2 => (objects[0].clone(), objects[1].clone()),
+            _ => {
+                let axis = bbox.longest_axis();
+                objects.sort_by(|a, b| {
+                    let a = a.bounding_box().axis(axis).min;
+                    let b = b.bounding_box().axis(axis).min;
+                    a.total_cmp(&b)
+                });
+
+                let (left, right) = objects.split_at_mut(objects.len() / 2);
+                (
+                    BvhChild::Node(Arc::new(Self::build(left))),
+                    BvhChild::Node(Arc::new(Self::build(right))),
+                )
+            }
+        };
+
+        Self { left, right, bbox }
+    }
+}
+
+impl BvhChild {
+    fn hit(&self, ray: &Ray, ray_t: Interval, record: &mut HitRecord) -> bool {
+        match self {
+            BvhChild::Node(node) => node.hit(ray, ray_t, record),
+            BvhChild::Leaf(object, id) => {
+                if object.hit(ray, ray_t, record) {
+                    record.object_id = *id;
+                    return true;
+                }
+                false
+            }
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        match self {
+            BvhChild::Node(node) => node.bbox,
+            BvhChild::Leaf(object, _) => object.bounding_box(),
+        }
+    }
+}
+
+impl Hittable for BvhNode {
+    fn hit(&self, ray: &Ray, ray_t: Interval, record: &mut HitRecord) -> bool {
+        if !self.bbox.hit(ray, ray_t) {
+            return false;
+        }
+
+        let hit_left = self.left.hit(ray, ray_t, record);
+        let right_t = Interval::new(
+            ray_t.min,
+            if hit_left { record.distance } else { ray_t.max },
+        );
+        let hit_right = self.right.hit(ray, right_t, record);
+
+        hit_left || hit_right
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
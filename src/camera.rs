@@ -3,17 +3,36 @@ use rand::random;
 use rayon::prelude::*;
 use std::io;
 use std::io::Write;
+use std::ops::Range;
+use std::sync::Arc;
 use tqdm::Iter;
 
 use crate::{
-    colour::{write_colour, Colour},
+    aov::{AovOutput, AovSample, Layers},
+    background::{Background, Gradient},
+    colour::{to_srgb8, write_colour, Colour},
     degrees_to_radians,
+    denoise::Denoiser,
     hittable::{HitRecord, Hittable},
+    image::Image,
+    lens::{Aperture, Lens},
+    light::Light,
+    material::ScatterSample,
+    medium::{Medium, MediumEvent},
+    png,
+    post::{Placement, PostEffect},
+    projection::Projection,
     ray::Ray,
+    shutter::Shutter,
+    spectrum::{sample_wavelength, wavelength_weight},
+    tonemap::ToneMap,
     Interval, Vector,
 };
 
-#[derive(Default, Clone, Copy)]
+/// The most times a ray may scatter inside a medium between two surface hits.
+const MAX_MEDIUM_STEPS: u32 = 10_000;
+
+#[derive(Default, Clone)]
 pub struct Camera {
     pub aspect_ratio: f64,
     pub width: u32,
@@ -25,7 +44,106 @@ pub struct Camera {
     pub vup: Vector<f64, 3>,
     pub defocus_angle: f64,
     pub focus_dist: f64,
+    /// If set, overrides `vfov` and `defocus_angle` with values derived from a physical lens.
+    pub lens: Option<Lens>,
+    pub aperture: Aperture,
+    pub projection: Projection,
+    /// Off-axis lens shift, as a fraction of the image width and height, moving the view
+    /// without changing the perspective.
+    pub shift_x: f64,
+    pub shift_y: f64,
+    /// If set, only this part of the image is rendered. The camera's view is unchanged.
+    pub crop: Option<CropWindow>,
+    /// Whether a cropped render is placed into an otherwise black image of the full size,
+    /// rather than written on its own.
+    pub crop_in_full_frame: bool,
+    pub exposure: f64,
+    pub tone_map: ToneMap,
+    pub post_effects: Vec<Arc<dyn PostEffect>>,
+    pub denoiser: Option<Denoiser>,
+    pub aov_output: Option<AovOutput>,
+    /// What rays see when they leave the scene. If unset, a sky gradient.
+    pub background: Option<Arc<dyn Background>>,
+    /// Traces a single random wavelength per sample, so that dispersive materials split light
+    /// into its colours.
+    pub spectral: bool,
+    /// Lights which aren't part of the scene, sampled directly from every hit.
+    pub lights: Vec<Arc<dyn Light>>,
+    /// The interval over which rays are spread in time. If unset, rays span `[0, 1)`.
+    pub shutter: Option<Shutter>,
+    /// Camera positions over time, in order of time. If empty, the camera stays at `lookfrom`,
+    /// facing `lookat`.
+    pub keyframes: Vec<CameraKeyframe>,
+    /// Hides the progress bar, as for worker processes sharing a terminal.
+    pub quiet: bool,
     height: u32,
+    viewport_width: f64,
+    viewport_height: f64,
+    defocus_radius: f64,
+    frame: Frame,
+}
+
+/// A rectangle of pixels, with its top left corner at `(x, y)`.
+#[derive(Clone, Copy, Debug)]
+pub struct CropWindow {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl CropWindow {
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Returns the part of the window which lies within an image of the given size.
+    pub fn clamped(&self, width: u32, height: u32) -> Self {
+        let x = self.x.min(width);
+        let y = self.y.min(height);
+        Self {
+            x,
+            y,
+            width: self.width.min(width - x),
+            height: self.height.min(height - y),
+        }
+    }
+
+    pub fn columns(&self) -> Range<u32> {
+        self.x..self.x + self.width
+    }
+
+    pub fn rows(&self) -> Range<u32> {
+        self.y..self.y + self.height
+    }
+}
+
+/// Where the camera is, and what it's looking at, at a given time.
+#[derive(Clone, Copy, Debug)]
+pub struct CameraKeyframe {
+    pub time: f64,
+    pub lookfrom: Point<f64, 3>,
+    pub lookat: Point<f64, 3>,
+}
+
+impl CameraKeyframe {
+    pub fn new(time: f64, lookfrom: Point<f64, 3>, lookat: Point<f64, 3>) -> Self {
+        Self {
+            time,
+            lookfrom,
+            lookat,
+        }
+    }
+}
+
+/// The camera's position and orientation at one instant.
+#[derive(Default, Clone, Copy)]
+struct Frame {
     centre: Point<f64, 3>,
     pixel_delta_v: Vector<f64, 3>,
     pixel_delta_u: Vector<f64, 3>,
@@ -41,134 +159,500 @@ impl Camera {
     pub fn render(&mut self, mut file: impl Write, world: impl Hittable + Clone + 'static) {
         self.initialise();
 
-        let mut data = String::new();
-
         // For logging
         let mut stderr = io::stderr();
 
-        let _ = file.write_fmt(format_args!("P3\n{} {}\n255\n", self.width, self.height));
+        let region = self.region();
+        let layers = self.render_region(&world, region.columns(), region.rows());
+        let layers = self.placed(layers);
+        self.write_image(&mut file, &layers);
+        self.write_aovs(&layers);
+
+        let _ = stderr.write(b"\rDone.                  \n");
+    }
 
-        let height = self.height;
-        let width = self.width;
+    /// The part of the image to render: the crop window, within the image, or else all of it.
+    /// The camera must have been initialised beforehand.
+    pub fn region(&self) -> CropWindow {
+        match self.crop {
+            Some(crop) => crop.clamped(self.width, self.height),
+            None => CropWindow::new(0, 0, self.width, self.height),
+        }
+    }
+
+    /// Places the rendered layers of `self.region()` into an otherwise black frame if
+    /// `crop_in_full_frame` is set, or else returns them as they are.
+    pub fn placed(&self, layers: Layers) -> Layers {
+        let region = self.region();
+        if !self.crop_in_full_frame || self.crop.is_none() {
+            return layers;
+        }
+        let mut frame = Layers::new(self.width, self.height);
+        frame.blit(region.x, region.y, &layers);
+        frame
+    }
+
+    /// Traces the rows in `rows`, returning the accumulated (not yet averaged) layers of each
+    /// pixel. The camera must have been initialised beforehand.
+    pub fn render_rows(&self, world: &impl Hittable, rows: Range<u32>) -> Layers {
+        self.render_region(world, 0..self.width, rows)
+    }
+
+    /// Traces the pixels in the rectangle given by `columns` and `rows`, returning their
+    /// accumulated layers. The camera must have been initialised beforehand.
+    pub fn render_region(
+        &self,
+        world: &impl Hittable,
+        columns: Range<u32>,
+        rows: Range<u32>,
+    ) -> Layers {
         let samples_per_pixel = self.samples_per_pixel;
         let max_depth = self.max_depth;
 
-        for j in (0..height).tqdm() {
-            for i in 0..width {
-                let pixel_colour: Colour = (0..samples_per_pixel)
+        let mut layers = Layers::new(columns.len() as u32, rows.len() as u32);
+
+        let rows: Box<dyn Iterator<Item = u32>> = if self.quiet {
+            Box::new(rows)
+        } else {
+            Box::new(rows.tqdm())
+        };
+        for (row, j) in rows.enumerate() {
+            for (column, i) in columns.clone().enumerate() {
+                let pixel_sample: AovSample = (0..samples_per_pixel)
                     .into_par_iter()
                     .map(|_| {
-                        let ray = self.get_ray(i, j);
-                        Self::ray_colour(ray, max_depth, &world)
+                        let mut ray = self.get_ray(i, j);
+                        let wavelength = self.spectral.then(sample_wavelength);
+                        ray = ray.with_wavelength(wavelength);
+
+                        let mut sample = AovSample::default();
+                        sample.colour =
+                            self.ray_colour(ray, max_depth, world, None, Some(&mut sample));
+                        if let Some(wavelength) = wavelength {
+                            let weight = wavelength_weight(wavelength);
+                            sample.colour = sample.colour.hadamard(weight);
+                            sample.direct = sample.direct.hadamard(weight);
+                            sample.indirect = sample.indirect.hadamard(weight);
+                        }
+                        sample
                     })
-                    .collect::<Vec<Colour>>()
+                    .collect::<Vec<AovSample>>()
                     .iter()
-                    .fold(Colour::zero(), |acc, c| acc + *c);
-                write_colour(&mut data, pixel_colour, samples_per_pixel);
+                    .fold(AovSample::default(), |acc, s| acc + *s);
+                layers.set(column as u32, row as u32, pixel_sample);
             }
         }
 
+        layers
+    }
+
+    /// Writes accumulated layers to `file` as a PPM of the beauty pass.
+    pub fn write_image(&self, file: impl Write, layers: &Layers) {
+        self.write_ppm(file, &self.develop(layers));
+    }
+
+    /// Writes an already developed image to `file` as a PPM.
+    pub fn write_ppm(&self, mut file: impl Write, image: &Image) {
+        let mut data = String::new();
+
+        let _ = file.write_fmt(format_args!("P3\n{} {}\n255\n", image.width, image.height));
+
+        for pixel_colour in &image.pixels {
+            write_colour(&mut data, *pixel_colour);
+        }
+
         file.write_all(data.as_bytes())
             .expect("Could not write to file");
-        let _ = stderr.write(b"\rDone.                  \n");
     }
 
-    fn initialise(&mut self) {
+    /// Writes accumulated layers to `file` as a PNG of the beauty pass.
+    pub fn write_png(&self, file: impl Write, layers: &Layers) {
+        self.write_png_image(file, &self.develop(layers));
+    }
+
+    /// Writes an already developed image to `file` as a PNG.
+    pub fn write_png_image(&self, file: impl Write, image: &Image) {
+        let data: Vec<u8> = image.pixels.iter().flat_map(|p| to_srgb8(*p)).collect();
+
+        png::write_png(file, image.width, image.height, &data).expect("Could not write to file");
+    }
+
+    /// Turns accumulated layers into a displayable, tone-mapped (but still linear) image.
+    pub fn develop(&self, layers: &Layers) -> Image {
+        let layers = layers.scaled(1. / self.samples_per_pixel as f64);
+        let mut image = match self.denoiser {
+            Some(denoiser) => denoiser.apply(&layers),
+            None => layers.beauty,
+        };
+
+        // Exposure is given in stops, so each EV doubles the brightness
+        image.scale(2f64.powf(self.exposure));
+
+        // A crop written on its own is still processed as part of the full frame
+        let placement = if image.width == self.width && image.height == self.height {
+            Placement::whole(&image)
+        } else {
+            let region = self.region();
+            Placement::new(region.x, region.y, self.width, self.height)
+        };
+        for effect in &self.post_effects {
+            effect.apply(&mut image, placement);
+        }
+
+        for pixel_colour in image.pixels.iter_mut() {
+            *pixel_colour = self.tone_map.apply(*pixel_colour);
+        }
+
+        image
+    }
+
+    /// Writes every layer to `self.aov_output`, if it is set.
+    pub fn write_aovs(&self, layers: &Layers) {
+        if let Some(output) = &self.aov_output {
+            layers
+                .scaled(1. / self.samples_per_pixel as f64)
+                .write(output)
+                .expect("Could not write AOVs");
+        }
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn initialise(&mut self) {
         // Calculate the image height, and ensure that it's at least 1.
         self.height = (self.width as f64 / self.aspect_ratio) as u32;
         if self.height < 1 {
             self.height = 1;
         }
 
-        self.centre = self.lookfrom;
+        if let Some(lens) = self.lens {
+            self.vfov = lens.vfov(self.width as f64 / self.height as f64);
+            self.defocus_angle = lens.defocus_angle(self.focus_dist);
+        }
 
         // Determine viewport dimensions
         let theta = degrees_to_radians(self.vfov);
         let h = (theta / 2.).tan();
-        let viewport_height = 2.0 * h * self.focus_dist;
-        let viewport_width = viewport_height * (self.width as f64 / self.height as f64);
+        self.viewport_height = 2.0 * h * self.focus_dist;
+        self.viewport_width = self.viewport_height * (self.width as f64 / self.height as f64);
+
+        self.defocus_radius = self.focus_dist * degrees_to_radians(self.defocus_angle / 2.).tan();
+
+        self.frame = self.frame(self.lookfrom, self.lookat);
+    }
+
+    fn frame(&self, lookfrom: Point<f64, 3>, lookat: Point<f64, 3>) -> Frame {
+        let mut frame = Frame {
+            centre: lookfrom,
+            ..Default::default()
+        };
 
         // Calculate the u,v,w basis vectors for the camera coordinate frame
-        self.w = (self.lookfrom - self.lookat).unit();
-        self.u = self.vup.cross(self.w).unit();
-        self.v = self.w.cross(self.u);
+        frame.w = (lookfrom - lookat).unit();
+        frame.u = self.vup.cross(frame.w).unit();
+        frame.v = frame.w.cross(frame.u);
 
         // Calculate vectors across horizontal and down vertical viewport edges
-        let viewport_u = viewport_width * self.u;
-        let viewport_v = viewport_height * -self.v;
+        let viewport_u = self.viewport_width * frame.u;
+        let viewport_v = self.viewport_height * -frame.v;
 
         // Calculate horizontal and vertical delta vectors from pixel to pixel
-        self.pixel_delta_u = viewport_u / self.width as f64;
-        self.pixel_delta_v = viewport_v / self.height as f64;
+        frame.pixel_delta_u = viewport_u / self.width as f64;
+        frame.pixel_delta_v = viewport_v / self.height as f64;
 
         // Calculate the location of the upper left pixel;
         let viewport_upper_left =
-            self.centre - (self.focus_dist * self.w) - viewport_u / 2. - viewport_v / 2.;
-        self.pixel00_loc = viewport_upper_left + (self.pixel_delta_u + self.pixel_delta_v) * 0.5;
+            frame.centre - (self.focus_dist * frame.w) - viewport_u / 2. - viewport_v / 2.
+                + (self.shift_x * viewport_u)
+                - (self.shift_y * viewport_v);
+        frame.pixel00_loc = viewport_upper_left + (frame.pixel_delta_u + frame.pixel_delta_v) * 0.5;
+
+        frame.defocus_disk_u = frame.u * self.defocus_radius;
+        frame.defocus_disk_v = frame.v * self.defocus_radius;
+
+        frame
+    }
+
+    /// Returns the camera's frame at `time`, interpolating linearly between keyframes.
+    fn frame_at(&self, time: f64) -> Frame {
+        let (Some(first), Some(last)) = (self.keyframes.first(), self.keyframes.last()) else {
+            return self.frame;
+        };
+
+        let next = self.keyframes.partition_point(|k| k.time <= time);
+        let (from, at) = if next == 0 {
+            (first.lookfrom, first.lookat)
+        } else if next == self.keyframes.len() {
+            (last.lookfrom, last.lookat)
+        } else {
+            let a = &self.keyframes[next - 1];
+            let b = &self.keyframes[next];
+            let t = (time - a.time) / (b.time - a.time);
+            (
+                a.lookfrom + (b.lookfrom - a.lookfrom) * t,
+                a.lookat + (b.lookat - a.lookat) * t,
+            )
+        };
 
-        let defocus_radius = self.focus_dist * degrees_to_radians(self.defocus_angle / 2.).tan();
-        self.defocus_disk_u = self.u * defocus_radius;
-        self.defocus_disk_v = self.v * defocus_radius;
+        self.frame(from, at)
     }
 
-    fn ray_colour(ray: Ray, depth: u32, world: &dyn Hittable) -> Colour {
+    /// Returns the colour seen along `ray`, which travels through `medium` if it is inside one.
+    /// If `aov` is given, it is filled in with what the ray hits first, and with how much of
+    /// its colour arrived directly from a light.
+    fn ray_colour(
+        &self,
+        ray: Ray,
+        depth: u32,
+        world: &dyn Hittable,
+        medium: Option<Medium>,
+        aov: Option<&mut AovSample>,
+    ) -> Colour {
         if depth == 0 {
             return Colour::new([0., 0., 0.]);
         }
 
+        let mut ray = ray;
         let mut record = HitRecord::default();
         // 0.001 is used rather than zero to prevent shadow acne
-        if world.hit(&ray, Interval::new(0.001, f64::INFINITY), &mut record) {
-            let mut attenuation = Colour::new([1., 1., 1.]);
+        let mut hit = world.hit(&ray, Interval::new(0.001, f64::INFINITY), &mut record);
+
+        // Inside a medium, light may scatter many times on its way to the surface, taking a
+        // random walk. Walks through bright media need hundreds of steps, so they have a budget
+        // of their own rather than using up `depth`.
+        let mut throughput = Colour::new([1., 1., 1.]);
+        if let Some(medium) = medium {
+            let mut steps = 0;
+            loop {
+                let speed = ray.direction().length();
+                let max_distance = if hit {
+                    record.distance * speed
+                } else {
+                    f64::INFINITY
+                };
+                let (distance, weight) = match medium.sample_distance(max_distance) {
+                    MediumEvent::Scatter { distance, weight } => (distance, weight),
+                    MediumEvent::Pass { weight } => {
+                        throughput = throughput.hadamard(weight);
+                        break;
+                    }
+                };
+                throughput = throughput.hadamard(weight);
+
+                // Russian roulette ends walks which carry little light, without biasing the rest
+                steps += 1;
+                if steps > MAX_MEDIUM_STEPS {
+                    return Colour::new([0., 0., 0.]);
+                }
+                if steps > 8 {
+                    let survival = throughput
+                        .x()
+                        .max(throughput.y())
+                        .max(throughput.z())
+                        .min(1.);
+                    if random::<f64>() >= survival {
+                        return Colour::new([0., 0., 0.]);
+                    }
+                    throughput = throughput / survival;
+                }
+
+                let direction = medium.sample_phase(ray.direction() / speed);
+                ray = Ray::new(ray.at(distance / speed), direction, Some(ray.time()))
+                    .with_wavelength(ray.wavelength());
+                record = HitRecord::default();
+                hit = world.hit(&ray, Interval::new(0.001, f64::INFINITY), &mut record);
+            }
+        }
+
+        if hit {
+            let sample = record.material.sample(&ray, &record);
+            let scatters = sample.is_some();
+
             let mut scattered = Ray::default();
-            if record
-                .material
-                .scatter(&ray, &record, &mut attenuation, &mut scattered)
-            {
-                let col_pt_2 = Camera::ray_colour(scattered, depth - 1, world);
-                let col = attenuation.hadamard(col_pt_2);
-
-                return col;
+            let mut weight = Colour::new([0., 0., 0.]);
+            let mut albedo = Colour::new([0., 0., 0.]);
+            let mut direct = record.material.emitted(&ray, &record);
+            if let Some(sample) = sample {
+                direct = direct + self.direct_lighting(&ray, &record, world);
+                albedo = sample.weight;
+                (scattered, weight) = self.sample_background(&ray, &record, sample);
+                scattered = scattered.with_wavelength(ray.wavelength());
             }
-            return Colour::new([0., 0., 0.]);
+            direct = throughput.hadamard(direct);
+            weight = throughput.hadamard(weight);
+
+            // Rays refracted into a closed surface travel through the medium filling it, until
+            // they refract out again
+            let transmits = scattered.direction().dot(&record.normal) < 0.;
+            let next_medium = match (transmits, record.front_face) {
+                (true, true) => record.material.medium(),
+                (true, false) => None,
+                (false, _) => medium,
+            };
+
+            let Some(aov) = aov else {
+                if scatters {
+                    let col_pt_2 = self.ray_colour(scattered, depth - 1, world, next_medium, None);
+                    return direct + weight.hadamard(col_pt_2);
+                }
+                return direct;
+            };
+
+            aov.albedo = albedo;
+            aov.normal = record.normal;
+            aov.depth = record.distance * ray.direction().length();
+            aov.hits = 1;
+            aov.object_id = record.object_id;
+            aov.material_id = record.material_id;
+
+            if scatters {
+                // Light is direct if the scattered ray goes straight to a light source
+                let mut next = AovSample::default();
+                let col_pt_2 =
+                    self.ray_colour(scattered, depth - 1, world, next_medium, Some(&mut next));
+                let col = weight.hadamard(col_pt_2);
+
+                if next.hits == 0 {
+                    aov.direct = direct + col;
+                } else {
+                    aov.direct = direct;
+                    aov.indirect = col;
+                }
+                return direct + col;
+            }
+            aov.direct = direct;
+            return direct;
         }
 
-        let unit_direction = ray.direction().unit();
-        let a = (unit_direction.y() + 1.0) * 0.5;
+        let background = match &self.background {
+            Some(background) => background.colour(ray.direction()),
+            None => Gradient::default().colour(ray.direction()),
+        };
+        let background = throughput.hadamard(background);
+        if let Some(aov) = aov {
+            aov.albedo = background;
+            aov.direct = background;
+        }
+        background
+    }
+
+    /// Returns the light reaching `record.p` from each of the camera's lights and leaving along
+    /// `ray_in`, by casting a shadow ray towards each.
+    fn direct_lighting(&self, ray_in: &Ray, record: &HitRecord, world: &dyn Hittable) -> Colour {
+        let mut total = Colour::new([0., 0., 0.]);
+        for light in &self.lights {
+            let Some(sample) = light.sample(record.p) else {
+                continue;
+            };
+            let reflected = record.material.eval(ray_in, record, sample.direction);
+            if reflected.near_zero() {
+                continue;
+            }
 
-        Colour::new([1.0, 1.0, 1.0]) * (1.0 - a) + Colour::new([0.5, 0.7, 1.0]) * a
+            let shadow_ray = Ray::new(record.p, sample.direction, Some(ray_in.time()));
+            let mut blocker = HitRecord::default();
+            let ray_t = Interval::new(0.001, sample.distance * (1. - 1e-6));
+            if world.hit(&shadow_ray, ray_t, &mut blocker) {
+                continue;
+            }
+            total = total + reflected.hadamard(sample.irradiance);
+        }
+        total
+    }
+
+    /// Under a background which can be importance sampled, sends half of the rays scattered by
+    /// non-delta lobes towards the brightest parts of the background instead. Returns the ray
+    /// to follow along with its weight.
+    fn sample_background(
+        &self,
+        ray_in: &Ray,
+        record: &HitRecord,
+        sample: ScatterSample,
+    ) -> (Ray, Colour) {
+        let material_ray = Ray::new(record.p, sample.direction, Some(ray_in.time()));
+        let Some(background) = &self.background else {
+            return (material_ray, sample.weight);
+        };
+        // Mirrors and smooth glass scatter in a single direction, so can't be aimed elsewhere
+        if sample.delta {
+            return (material_ray, sample.weight);
+        }
+        let Some(light_direction) = background.sample_direction() else {
+            return (material_ray, sample.weight);
+        };
+
+        let direction = if random::<f64>() < 0.5 {
+            light_direction.unit()
+        } else {
+            sample.direction
+        };
+
+        // Weight by the BSDF over the density of the mixture of strategies
+        let material_pdf = record.material.pdf(ray_in, record, direction);
+        let pdf = 0.5 * background.pdf(direction) + 0.5 * material_pdf;
+        let scattered = Ray::new(record.p, direction, Some(ray_in.time()));
+        if pdf <= 0. {
+            return (scattered, Colour::new([0., 0., 0.]));
+        }
+        (
+            scattered,
+            record.material.eval(ray_in, record, direction) / pdf,
+        )
     }
 
     // Get a randomly sampled camera ray for te pixel at location i,j
     fn get_ray(&self, i: u32, j: u32) -> Ray {
+        let ray_time = match self.shutter {
+            Some(shutter) => shutter.sample(),
+            None => random::<f64>(),
+        };
+        let frame = self.frame_at(ray_time);
+
+        let s = (i as f64 + random::<f64>()) / self.width as f64;
+        let t = (j as f64 + random::<f64>()) / self.height as f64;
+        let aspect_ratio = self.width as f64 / self.height as f64;
+        if let Some(d) = self.projection.direction(s, t, aspect_ratio, self.vfov) {
+            let to_world =
+                |v: Vector<f64, 3>| (v.x() * frame.u) + (v.y() * frame.v) - (v.z() * frame.w);
+            let (offset, d) = self.projection.eye_offset(s, d);
+            return Ray::new(frame.centre + to_world(offset), to_world(d), Some(ray_time));
+        }
+
         // Constructs a camera ray originating from the defocus disk and directed at a randomly
         // sampled point around the pixel location i, j.
 
         let pixel_centre =
-            self.pixel00_loc + (self.pixel_delta_u * i as f64) + (self.pixel_delta_v * j as f64);
-        let pixel_sample = pixel_centre + self.pixel_sample_square();
+            frame.pixel00_loc + (frame.pixel_delta_u * i as f64) + (frame.pixel_delta_v * j as f64);
+        let pixel_sample = pixel_centre + Self::pixel_sample_square(&frame);
+
+        if let Projection::Orthographic = self.projection {
+            // Shift the focal plane back onto the camera, and look straight ahead
+            let ray_origin = pixel_sample + (self.focus_dist * frame.w);
+            return Ray::new(ray_origin, -frame.w, Some(ray_time));
+        }
 
         let ray_origin = if self.defocus_angle <= 0. {
-            self.centre
+            frame.centre
         } else {
-            self.defocus_disk_sample()
+            self.defocus_disk_sample(&frame)
         };
         let ray_direction = pixel_sample - ray_origin;
-        let ray_time = random::<f64>();
 
         Ray::new(ray_origin, ray_direction, Some(ray_time))
     }
 
     /// Returns a random point in the square surrounding a pixel at the origin
-    fn pixel_sample_square(&self) -> Vector<f64, 3> {
+    fn pixel_sample_square(frame: &Frame) -> Vector<f64, 3> {
         let px = -0.5 * random::<f64>();
         let py = -0.5 * random::<f64>();
-        (px * self.pixel_delta_u) + (py * self.pixel_delta_v)
+        (px * frame.pixel_delta_u) + (py * frame.pixel_delta_v)
     }
 
-    /// Returns a random point in the camera defocus disk
-    fn defocus_disk_sample(&self) -> Point<f64, 3> {
-        let p = Vector::random_in_unit_disk();
-        self.centre + (p.x() * self.defocus_disk_u) + (p.y() * self.defocus_disk_v)
+    /// Returns a random point in the camera defocus disk, shaped by the aperture
+    fn defocus_disk_sample(&self, frame: &Frame) -> Point<f64, 3> {
+        let p = self.aperture.sample();
+        frame.centre + (p.x() * frame.defocus_disk_u) + (p.y() * frame.defocus_disk_v)
     }
 }
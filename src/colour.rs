@@ -2,31 +2,56 @@ use crate::{Interval, Vector};
 
 pub type Colour = Vector<f64, 3>;
 
-fn linear_to_gamma(linear_component: f64) -> f64 {
-    if linear_component > 0.0 {
-        return linear_component.sqrt();
+/// Applies the sRGB transfer function to a linear colour component.
+pub fn linear_to_srgb(linear_component: f64) -> f64 {
+    if linear_component <= 0.0 {
+        return 0.0;
     }
-    0.0
+    if linear_component <= 0.003_130_8 {
+        return 12.92 * linear_component;
+    }
+    1.055 * linear_component.powf(1.0 / 2.4) - 0.055
 }
 
-pub fn write_colour(out: &mut String, pixel_colour: Colour, samples_per_pixel: u32) {
-    let pixel_colour = pixel_colour * (1. / samples_per_pixel as f64);
-    let mut r = pixel_colour.x();
-    let mut g = pixel_colour.y();
-    let mut b = pixel_colour.z();
+/// Inverts `linear_to_srgb`.
+pub fn srgb_to_linear(srgb_component: f64) -> f64 {
+    if srgb_component <= 0.040_45 {
+        return srgb_component / 12.92;
+    }
+    ((srgb_component + 0.055) / 1.055).powf(2.4)
+}
 
-    r = linear_to_gamma(r);
-    g = linear_to_gamma(g);
-    b = linear_to_gamma(b);
+/// Encodes a tone-mapped linear colour as 8-bit sRGB.
+pub fn to_srgb8(pixel_colour: Colour) -> [u8; 3] {
+    let r = linear_to_srgb(pixel_colour.x());
+    let g = linear_to_srgb(pixel_colour.y());
+    let b = linear_to_srgb(pixel_colour.z());
 
     static INTENSITY: Interval = Interval::new(0.000, 0.999);
-    out.push_str(
-        format!(
-            "{} {} {}\n",
-            (255. * INTENSITY.clamp(r)) as u8,
-            (255. * INTENSITY.clamp(g)) as u8,
-            (255. * INTENSITY.clamp(b)) as u8
-        )
-        .as_str(),
-    )
+    [
+        (255. * INTENSITY.clamp(r)) as u8,
+        (255. * INTENSITY.clamp(g)) as u8,
+        (255. * INTENSITY.clamp(b)) as u8,
+    ]
+}
+
+/// Writes a tone-mapped linear colour as an 8-bit sRGB PPM triple.
+pub fn write_colour(out: &mut String, pixel_colour: Colour) {
+    let [r, g, b] = to_srgb8(pixel_colour);
+    out.push_str(format!("{} {} {}\n", r, g, b).as_str())
+}
+
+/// Returns the relative luminance of a linear (Rec. 709 primaries) colour.
+pub fn luminance(colour: Colour) -> f64 {
+    0.2126 * colour.x() + 0.7152 * colour.y() + 0.0722 * colour.z()
+}
+
+/// Converts a CIE 1931 XYZ colour to linear sRGB (Rec. 709 primaries, D65 white).
+pub fn xyz_to_linear_srgb(xyz: Vector<f64, 3>) -> Colour {
+    let (x, y, z) = (xyz.x(), xyz.y(), xyz.z());
+    Colour::new([
+        3.2406 * x - 1.5372 * y - 0.4986 * z,
+        -0.9689 * x + 1.8758 * y + 0.0415 * z,
+        0.0557 * x - 0.2040 * y + 1.0570 * z,
+    ])
 }
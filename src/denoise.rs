@@ -0,0 +1,149 @@
+use crate::{aov::Layers, colour::Colour, image::Image};
+
+/// An edge-avoiding à-trous wavelet filter (Dammertz et al. 2010), guided by the albedo and
+/// normal buffers so that it blurs noise without blurring across edges or textures.
+#[derive(Clone, Copy, Debug)]
+pub struct Denoiser {
+    pub iterations: u32,
+    pub colour_sigma: f64,
+    pub normal_sigma: f64,
+    pub albedo_sigma: f64,
+}
+
+impl Default for Denoiser {
+    fn default() -> Self {
+        Self {
+            iterations: 5,
+            colour_sigma: 0.5,
+            normal_sigma: 0.1,
+            albedo_sigma: 0.1,
+        }
+    }
+}
+
+impl Denoiser {
+    /// Returns a denoised copy of the (averaged) beauty layer.
+    pub fn apply(&self, layers: &Layers) -> Image {
+        // Filter the irradiance rather than the colour, so that texture detail carried by the
+        // albedo survives intact.
+        let mut irradiance = layers.beauty.clone();
+        for (pixel, albedo) in irradiance.pixels.iter_mut().zip(&layers.albedo.pixels) {
+            *pixel = demodulate(*pixel, *albedo);
+        }
+
+        let mut colour_sigma = self.colour_sigma;
+        for iteration in 0..self.iterations {
+            irradiance = self.filter(&irradiance, layers, 1 << iteration, colour_sigma);
+            // Each pass sees less noise, so the colour threshold tightens as we go
+            colour_sigma /= 2.;
+        }
+
+        for (pixel, albedo) in irradiance.pixels.iter_mut().zip(&layers.albedo.pixels) {
+            *pixel = remodulate(*pixel, *albedo);
+        }
+        irradiance
+    }
+
+    fn filter(&self, source: &Image, layers: &Layers, step: i64, colour_sigma: f64) -> Image {
+        // B3 spline
+        const KERNEL: [f64; 5] = [1. / 16., 1. / 4., 3. / 8., 1. / 4., 1. / 16.];
+
+        let mut out = Image::new(source.width, source.height);
+
+        for j in 0..source.height {
+            for i in 0..source.width {
+                let colour = source.get(i, j);
+                let normal = layers.normal.get(i, j);
+                let albedo = layers.albedo.get(i, j);
+
+                let mut sum = Colour::zero();
+                let mut total_weight = 0.0;
+
+                for (dy, ky) in KERNEL.iter().enumerate() {
+                    for (dx, kx) in KERNEL.iter().enumerate() {
+                        let x = i as i64 + (dx as i64 - 2) * step;
+                        let y = j as i64 + (dy as i64 - 2) * step;
+                        if x < 0 || y < 0 || x >= source.width as i64 || y >= source.height as i64 {
+                            continue;
+                        }
+                        let (x, y) = (x as u32, y as u32);
+
+                        let sample = source.get(x, y);
+                        let colour_weight = edge_stop(colour - sample, colour_sigma);
+                        let normal_weight =
+                            edge_stop(normal - layers.normal.get(x, y), self.normal_sigma);
+                        let albedo_weight =
+                            edge_stop(albedo - layers.albedo.get(x, y), self.albedo_sigma);
+
+                        let weight = kx * ky * colour_weight * normal_weight * albedo_weight;
+                        sum = sum + sample * weight;
+                        total_weight += weight;
+                    }
+                }
+
+                out.set(i, j, sum / total_weight);
+            }
+        }
+
+        out
+    }
+}
+
+fn edge_stop(difference: Colour, sigma: f64) -> f64 {
+    (-difference.length_squared() / (sigma * sigma)).exp()
+}
+
+const EPSILON: f64 = 1e-3;
+
+fn demodulate(colour: Colour, albedo: Colour) -> Colour {
+    Colour::new([
+        colour.x() / (albedo.x() + EPSILON),
+        colour.y() / (albedo.y() + EPSILON),
+        colour.z() / (albedo.z() + EPSILON),
+    ])
+}
+
+fn remodulate(irradiance: Colour, albedo: Colour) -> Colour {
+    irradiance.hadamard(albedo + Colour::new([EPSILON, EPSILON, EPSILON]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn constant_images_are_left_alone() {
+        let mut layers = Layers::new(16, 12);
+        layers.beauty.pixels.fill(Colour::new([0.3, 0.6, 0.9]));
+        layers.albedo.pixels.fill(Colour::new([0.5, 0.5, 0.5]));
+        layers.normal.pixels.fill(Colour::new([0., 0., 1.]));
+
+        let denoised = Denoiser::default().apply(&layers);
+        for (pixel, original) in denoised.pixels.iter().zip(&layers.beauty.pixels) {
+            assert!((*pixel - *original).length() < 1e-9, "{pixel:?}");
+        }
+    }
+
+    #[test]
+    fn textures_under_even_lighting_are_left_alone() {
+        // A checkerboard albedo under uniform light
+        let mut layers = Layers::new(16, 12);
+        layers.normal.pixels.fill(Colour::new([0., 0., 1.]));
+        for j in 0..12 {
+            for i in 0..16 {
+                let albedo = if (i / 2 + j / 2) % 2 == 0 {
+                    Colour::new([0.8, 0.2, 0.2])
+                } else {
+                    Colour::new([0.1, 0.1, 0.7])
+                };
+                layers.albedo.set(i, j, albedo);
+                layers.beauty.set(i, j, albedo * 2.);
+            }
+        }
+
+        let denoised = Denoiser::default().apply(&layers);
+        for (pixel, original) in denoised.pixels.iter().zip(&layers.beauty.pixels) {
+            assert!((*pixel - *original).length() < 1e-9, "{pixel:?}");
+        }
+    }
+}
@@ -1,15 +1,55 @@
 use linalg::vector::Vector;
 use rand::random;
 
-use crate::{colour::Colour, material::Material, Ray};
+use crate::{
+    colour::Colour,
+    hittable::HitRecord,
+    material::{Material, ScatterSample},
+    microfacet::Ggx,
+    onb::Onb,
+    spectrum::Dispersion,
+    Ray,
+};
 
 pub struct Dielectric {
-    refractive_index: f64,
+    pub refractive_index: f64,
+    /// From 0 (polished) to 1 (frosted).
+    pub roughness: f64,
+    /// Fraction of each of red, green and blue absorbed per unit distance travelled inside.
+    /// See `Dielectric::absorption_for`.
+    pub absorption: Colour,
+    /// Treats the surface as a sheet with no thickness, like a window pane, so that light passes
+    /// straight through rather than refracting.
+    pub thin_walled: bool,
+    /// How the refractive index varies with wavelength, which takes the place of
+    /// `refractive_index` when rendering spectrally.
+    pub dispersion: Option<Dispersion>,
 }
 
 impl Dielectric {
     pub fn new(refractive_index: f64) -> Self {
-        Self { refractive_index }
+        Self {
+            refractive_index,
+            roughness: 0.,
+            absorption: Colour::new([0., 0., 0.]),
+            thin_walled: false,
+            dispersion: None,
+        }
+    }
+
+    /// The absorption coefficients with which light keeps `colour` of its intensity after
+    /// travelling `distance` through the material, which must be positive.
+    pub fn absorption_for(colour: Colour, distance: f64) -> Colour {
+        assert!(
+            distance > 0.,
+            "Absorption needs a positive distance, not {distance}"
+        );
+        let coefficient = |c: f64| -c.max(1e-6).ln() / distance;
+        Colour::new([
+            coefficient(colour.x()),
+            coefficient(colour.y()),
+            coefficient(colour.z()),
+        ])
     }
 
     fn reflectance(cosine: f64, refractive_index: f64) -> f64 {
@@ -18,38 +58,277 @@ impl Dielectric {
 
         r0 + (1. - r0) * (1. - cosine).powi(5)
     }
+
+    /// The ratio of refractive indices across the surface, from the side `ray_in` arrives on
+    /// to the other.
+    fn ratio(&self, ray_in: &Ray, record: &HitRecord) -> f64 {
+        let refractive_index = match (self.dispersion, ray_in.wavelength()) {
+            (Some(dispersion), Some(wavelength)) => dispersion.refractive_index(wavelength),
+            _ => self.refractive_index,
+        };
+        if record.front_face || self.thin_walled {
+            1.0 / refractive_index
+        } else {
+            refractive_index
+        }
+    }
+
+    /// The fraction of light reflected at `cos_theta` to the normal, given the ratio `ri` of
+    /// refractive indices.
+    fn fresnel(&self, cos_theta: f64, ri: f64) -> f64 {
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.).sqrt();
+        if ri * sin_theta > 1.0 {
+            return 1.;
+        }
+        let reflectance = Self::reflectance(cos_theta, ri);
+        if self.thin_walled {
+            // Light bounces back and forth between the two faces of the sheet
+            return 2. * reflectance / (1. + reflectance);
+        }
+        reflectance
+    }
+
+    /// The microfacet distribution, or `None` for a smooth surface.
+    fn distribution(&self) -> Option<Ggx> {
+        let distribution = Ggx::new(self.roughness, 0.);
+        (!distribution.is_smooth()).then_some(distribution)
+    }
+
+    /// The fraction of light surviving the distance travelled inside to reach `record`.
+    fn transmittance(&self, ray_in: &Ray, record: &HitRecord) -> Colour {
+        if record.front_face || self.thin_walled {
+            return Colour::new([1., 1., 1.]);
+        }
+        let distance = record.distance * ray_in.direction().length();
+        Colour::new([
+            (-self.absorption.x() * distance).exp(),
+            (-self.absorption.y() * distance).exp(),
+            (-self.absorption.z() * distance).exp(),
+        ])
+    }
+
+    /// For a rough surface, finds the local microfacet normal which would scatter light from
+    /// `direction` along `ray_in` reversed. Returns it along with the Fresnel reflectance there
+    /// and the change of density from microfacet normals to `direction`.
+    fn half_vector(
+        &self,
+        ray_in: &Ray,
+        record: &HitRecord,
+        direction: Vector<f64, 3>,
+    ) -> Option<(Vector<f64, 3>, f64, f64)> {
+        self.distribution()?;
+        let onb = Onb::new(record.normal);
+        let wo = onb.to_local(-ray_in.direction().unit());
+        let wi = onb.to_local(direction);
+        if wo.z() <= 0. || wi.z() == 0. {
+            return None;
+        }
+
+        let ri = self.ratio(ray_in, record);
+        if wi.z() > 0. {
+            let h = (wo + wi).unit();
+            let reflectance = self.fresnel(wo.dot(&h), ri);
+            return Some((h, reflectance, 1. / (4. * wo.dot(&h))));
+        }
+        if self.thin_walled {
+            // Light passes straight through, which only a delta lobe can do
+            return None;
+        }
+
+        // The generalised half vector for refraction, facing the same way as the normal
+        let eta = 1. / ri;
+        let mut h = (wo + wi * eta).unit();
+        if h.z() < 0. {
+            h = -h;
+        }
+        let (o, i) = (wo.dot(&h), wi.dot(&h));
+        if o <= 0. || i >= 0. {
+            return None;
+        }
+
+        let denominator = (i + o / eta).powi(2);
+        let reflectance = self.fresnel(o, ri);
+        Some((h, reflectance, i.abs() / denominator))
+    }
 }
 
 impl Material for Dielectric {
-    fn scatter(
-        &self,
-        ray_in: &crate::Ray,
-        record: &crate::hittable::HitRecord,
-        attenuation: &mut crate::colour::Colour,
-        scattered: &mut crate::Ray,
-    ) -> bool {
-        *attenuation = Colour::new([1.0, 1.0, 1.0]);
-        let ri = if record.front_face {
-            1.0 / self.refractive_index
+    fn sample(&self, ray_in: &Ray, record: &HitRecord) -> Option<ScatterSample> {
+        let transmittance = self.transmittance(ray_in, record);
+        let ri = self.ratio(ray_in, record);
+        let unit_direction = ray_in.direction().unit();
+        let onb = Onb::new(record.normal);
+        let wo = onb.to_local(-unit_direction);
+
+        // Reflect and refract about a microfacet normal rather than the surface's, unless the
+        // surface is smooth
+        let distribution = self.distribution().filter(|_| wo.z() > 0.);
+        let normal = match distribution {
+            Some(distribution) => onb.to_world(distribution.sample_visible_normal(wo)),
+            None => record.normal,
+        };
+
+        let cos_theta = (-unit_direction).dot(&normal).min(1.0);
+        let reflectance = self.fresnel(cos_theta, ri);
+        let reflects = reflectance > random::<f64>();
+        let direction = if reflects {
+            Vector::reflect(unit_direction, normal)
+        } else if self.thin_walled {
+            unit_direction
+        } else {
+            Vector::refract(&unit_direction, &normal, ri).unit()
+        };
+        let lobe = if reflects {
+            reflectance
         } else {
-            self.refractive_index
+            1. - reflectance
         };
 
-        let unit_direction = ray_in.direction().unit();
+        let Some(distribution) = distribution else {
+            return Some(ScatterSample {
+                direction,
+                weight: transmittance,
+                pdf: lobe,
+                delta: true,
+            });
+        };
+
+        // Rays scattered to the wrong side of the surface are lost to the microfacets blocking
+        // them, and the rest are weighted to make up for it
+        let wi = onb.to_local(direction);
+        if (wi.z() > 0.) != reflects {
+            return None;
+        }
+        let masking = distribution.g2(wo, wi) / distribution.g1(wo);
+        let delta = self.thin_walled && !reflects;
+        Some(ScatterSample {
+            direction,
+            weight: transmittance * masking,
+            pdf: if delta {
+                lobe
+            } else {
+                self.pdf(ray_in, record, direction)
+            },
+            delta,
+        })
+    }
+
+    fn eval(&self, ray_in: &Ray, record: &HitRecord, direction: Vector<f64, 3>) -> Colour {
+        let Some((h, reflectance, jacobian)) = self.half_vector(ray_in, record, direction) else {
+            return Colour::new([0., 0., 0.]);
+        };
+        let distribution = Ggx::new(self.roughness, 0.);
+        let onb = Onb::new(record.normal);
+        let wo = onb.to_local(-ray_in.direction().unit());
+        let wi = onb.to_local(direction);
 
-        let cos_theta = (-unit_direction).dot(&record.normal).min(1.0);
-        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+        // The cosine of the incoming light cancels with the BSDF's denominator
+        let d = distribution.d(h);
+        let g = distribution.g2(wo, wi);
+        let f = if wi.z() > 0. {
+            reflectance * d * g / (4. * wo.z())
+        } else {
+            (1. - reflectance) * d * g * wo.dot(&h) * jacobian / wo.z()
+        };
+        self.transmittance(ray_in, record) * f
+    }
 
-        let cannot_refract = ri * sin_theta > 1.0;
+    fn pdf(&self, ray_in: &Ray, record: &HitRecord, direction: Vector<f64, 3>) -> f64 {
+        let Some((h, reflectance, jacobian)) = self.half_vector(ray_in, record, direction) else {
+            return 0.;
+        };
+        let distribution = Ggx::new(self.roughness, 0.);
+        let wo = Onb::new(record.normal).to_local(-ray_in.direction().unit());
+        let normal_pdf = distribution.visible_normal_pdf(wo, h);
 
-        let direction = if cannot_refract || Self::reflectance(cos_theta, ri) > random::<f64>() {
-            Vector::reflect(unit_direction, record.normal)
+        if h.dot(&Onb::new(record.normal).to_local(direction)) > 0. {
+            reflectance * normal_pdf * jacobian
         } else {
-            Vector::refract(&unit_direction, &record.normal, ri)
+            (1. - reflectance) * normal_pdf * jacobian
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use linalg::Point;
+
+    /// A ray arriving at `degrees` from the normal of a surface facing +z.
+    fn arriving_at(degrees: f64, front_face: bool) -> (Ray, HitRecord) {
+        let angle = degrees.to_radians();
+        let direction = Vector::new([angle.sin(), 0., -angle.cos()]);
+        let ray = Ray::new(Point::new([0., 0., 0.]) - direction, direction, None);
+        let record = HitRecord {
+            normal: Vector::new([0., 0., 1.]),
+            distance: 1.,
+            front_face,
+            ..Default::default()
         };
+        (ray, record)
+    }
+
+    fn rough_glass() -> Dielectric {
+        let mut glass = Dielectric::new(1.5);
+        glass.roughness = 0.5;
+        glass
+    }
 
-        *scattered = Ray::new(record.p, direction, Some(ray_in.time()));
+    #[test]
+    fn absorption_keeps_the_colour_over_the_distance() {
+        let colour = Colour::new([0.9, 0.5, 0.1]);
+        let absorption = Dielectric::absorption_for(colour, 2.);
+        let kept = |a: f64| (-a * 2.).exp();
+        assert!((kept(absorption.x()) - 0.9).abs() < 1e-12);
+        assert!((kept(absorption.y()) - 0.5).abs() < 1e-12);
+        assert!((kept(absorption.z()) - 0.1).abs() < 1e-12);
+    }
+
+    #[test]
+    #[should_panic]
+    fn absorption_needs_a_distance() {
+        Dielectric::absorption_for(Colour::new([0.5, 0.5, 0.5]), 0.);
+    }
+
+    #[test]
+    fn rough_samples_agree_with_eval_and_pdf() {
+        let glass = rough_glass();
+        for front_face in [true, false] {
+            let (ray, record) = arriving_at(40., front_face);
+            for _ in 0..10_000 {
+                let Some(sample) = glass.sample(&ray, &record) else {
+                    continue;
+                };
+                let pdf = glass.pdf(&ray, &record, sample.direction);
+                assert!(pdf > 0.);
+                assert!(
+                    (sample.pdf - pdf).abs() <= 1e-9 * pdf,
+                    "{} {pdf}",
+                    sample.pdf
+                );
+
+                // The sample's weight is the BSDF times the cosine over the pdf
+                let expected = glass.eval(&ray, &record, sample.direction) / pdf;
+                assert!((sample.weight - expected).length() <= 1e-9 * expected.length());
+            }
+        }
+    }
 
-        true
+    #[test]
+    fn rough_glass_never_creates_energy() {
+        let glass = rough_glass();
+        const SAMPLES: usize = 20_000;
+        for front_face in [true, false] {
+            for degrees in [0., 30., 60., 85.] {
+                let (ray, record) = arriving_at(degrees, front_face);
+                let total: f64 = (0..SAMPLES)
+                    .filter_map(|_| glass.sample(&ray, &record))
+                    .map(|sample| sample.weight.x())
+                    .sum();
+                let albedo = total / SAMPLES as f64;
+                assert!(albedo <= 1.01, "{degrees}° {front_face}: {albedo}");
+                assert!(albedo > 0.5, "{degrees}° {front_face}: {albedo}");
+            }
+        }
     }
 }
@@ -0,0 +1,275 @@
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    process::{Command, Stdio},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+use crate::{aov::Layers, camera::Camera, colour::Colour, hittable::Hittable, image::Image};
+
+/// Splits a render into bands of rows and farms them out to worker processes.
+///
+/// Each worker is started as `program args...` and speaks a simple protocol over its stdin and
+/// stdout (see [`serve`]), so any command that ends up running [`serve`] with the same scene and
+/// camera will do, e.g. `ssh host raytracer --worker` to borrow another machine's cores.
+pub struct Coordinator {
+    pub program: String,
+    pub args: Vec<String>,
+    pub workers: u32,
+    pub rows_per_job: u32,
+}
+
+impl Coordinator {
+    pub fn new(program: String, args: Vec<String>, workers: u32) -> Self {
+        Self {
+            program,
+            args,
+            workers,
+            rows_per_job: 8,
+        }
+    }
+
+    /// Renders the camera's view of the scene on the workers, writing it to `file`. Fails if any
+    /// worker can't be started, exits early or sends back something other than what was asked.
+    pub fn render(&self, camera: &mut Camera, mut file: impl Write) -> io::Result<()> {
+        camera.initialise();
+
+        // Only the crop window is farmed out, if there is one
+        let region = camera.region();
+        let rows_per_job = self.rows_per_job.max(1);
+        let jobs = region.height.div_ceil(rows_per_job);
+
+        let next_job = AtomicU32::new(0);
+        let layers = Mutex::new(Layers::new(region.width, region.height));
+
+        let run_worker = || -> io::Result<()> {
+            let mut child = Command::new(&self.program)
+                .args(&self.args)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .spawn()?;
+
+            let mut input = child.stdin.take().expect("Worker stdin is piped");
+            let mut output = BufReader::new(child.stdout.take().expect("Worker stdout is piped"));
+
+            let mut run_jobs = || -> io::Result<()> {
+                loop {
+                    let job = next_job.fetch_add(1, Ordering::Relaxed);
+                    if job >= jobs {
+                        break;
+                    }
+                    let start = region.y + job * rows_per_job;
+                    let end = (start + rows_per_job).min(region.y + region.height);
+
+                    writeln!(input, "{start} {end}")?;
+                    input.flush()?;
+
+                    let rows = read_layers(&mut output)?;
+                    let expected = (region.width, end - start);
+                    if rows
+                        .images()
+                        .iter()
+                        .any(|i| (i.width, i.height) != expected)
+                    {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "Worker sent rows of the wrong size",
+                        ));
+                    }
+                    layers.lock().unwrap().blit_rows(start - region.y, &rows);
+                }
+                Ok(())
+            };
+            let result = run_jobs();
+
+            // Closing stdin tells the worker there is no more work. A worker which died is the
+            // likely cause of any error above, so its exit status is the more useful report.
+            drop(input);
+            let status = child.wait()?;
+            if !status.success() {
+                return Err(io::Error::other(format!("Worker exited with {status}")));
+            }
+            result
+        };
+
+        thread::scope(|scope| {
+            let workers: Vec<_> = (0..self.workers.max(1))
+                .map(|_| scope.spawn(run_worker))
+                .collect();
+            workers
+                .into_iter()
+                .map(|worker| worker.join().expect("Worker thread panicked"))
+                .collect::<io::Result<Vec<()>>>()
+        })?;
+
+        let layers = camera.placed(layers.into_inner().unwrap());
+        camera.write_image(&mut file, &layers);
+        camera.write_aovs(&layers);
+        Ok(())
+    }
+}
+
+/// Runs a render worker, answering each `<start> <end>` line read from `input` with the
+/// accumulated layers of those rows, until `input` is closed. Only the columns within the
+/// camera's crop window are rendered, if it has one. Progress isn't shown, since many workers
+/// share the coordinator's terminal.
+pub fn serve(
+    camera: &mut Camera,
+    world: impl Hittable,
+    input: impl Read,
+    mut output: impl Write,
+) -> io::Result<()> {
+    camera.initialise();
+    camera.quiet = true;
+
+    for line in BufReader::new(input).lines() {
+        let line = line?;
+        let mut bounds = line.split_whitespace().map(|n| n.parse::<u32>());
+        let (Some(Ok(start)), Some(Ok(end))) = (bounds.next(), bounds.next()) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Malformed job: {line:?}"),
+            ));
+        };
+
+        let rows = camera.render_region(&world, camera.region().columns(), start..end);
+        write_layers(&mut output, &rows)?;
+        output.flush()?;
+    }
+    Ok(())
+}
+
+fn write_layers(out: &mut impl Write, layers: &Layers) -> io::Result<()> {
+    for image in layers.images() {
+        write_image(out, image)?;
+    }
+    Ok(())
+}
+
+fn read_layers(input: &mut impl Read) -> io::Result<Layers> {
+    let mut layers = Layers::default();
+    for image in layers.images_mut() {
+        *image = read_image(input)?;
+    }
+    Ok(layers)
+}
+
+fn write_image(out: &mut impl Write, image: &Image) -> io::Result<()> {
+    out.write_all(&image.width.to_le_bytes())?;
+    out.write_all(&image.height.to_le_bytes())?;
+    for pixel in &image.pixels {
+        for component in [pixel.x(), pixel.y(), pixel.z()] {
+            out.write_all(&component.to_le_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+fn read_image(input: &mut impl Read) -> io::Result<Image> {
+    let mut word = [0u8; 4];
+    input.read_exact(&mut word)?;
+    let width = u32::from_le_bytes(word);
+    input.read_exact(&mut word)?;
+    let height = u32::from_le_bytes(word);
+
+    let mut image = Image::new(width, height);
+    let mut component = [0u8; 8];
+    for pixel in image.pixels.iter_mut() {
+        let mut rgb = [0.0; 3];
+        for c in rgb.iter_mut() {
+            input.read_exact(&mut component)?;
+            *c = f64::from_le_bytes(component);
+        }
+        *pixel = Colour::new(rgb);
+    }
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{hittable_list::HittableList, sphere::Sphere};
+    use linalg::{vector::Vector, Point};
+
+    fn layers() -> Layers {
+        let mut layers = Layers::new(3, 2);
+        for (n, image) in layers.images_mut().into_iter().enumerate() {
+            for (k, pixel) in image.pixels.iter_mut().enumerate() {
+                let x = (n * 10 + k) as f64;
+                *pixel = Colour::new([x, -x, x / 7.]);
+            }
+        }
+        layers
+    }
+
+    #[test]
+    fn layers_round_trip() {
+        let mut bytes = Vec::new();
+        write_layers(&mut bytes, &layers()).unwrap();
+        // Each of the 8 layers has a width, a height and 3 channels of 6 pixels
+        assert_eq!(bytes.len(), 8 * (8 + 3 * 6 * 8));
+
+        let read = read_layers(&mut bytes.as_slice()).unwrap();
+        for (a, b) in read.images().iter().zip(layers().images()) {
+            assert_eq!((a.width, a.height), (3, 2));
+            assert_eq!(a.pixels, b.pixels);
+        }
+    }
+
+    #[test]
+    fn truncated_layers_are_an_error() {
+        let mut bytes = Vec::new();
+        write_layers(&mut bytes, &layers()).unwrap();
+        bytes.pop();
+        let error = read_layers(&mut bytes.as_slice()).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn bands_are_placed_at_their_rows() {
+        let mut layers = Layers::new(3, 4);
+        layers.blit_rows(1, &self::layers());
+        let band = self::layers();
+        assert_eq!(layers.beauty.get(0, 0), Colour::default());
+        assert_eq!(layers.beauty.get(2, 1), band.beauty.get(2, 0));
+        assert_eq!(layers.normal.get(1, 2), band.normal.get(1, 1));
+        assert_eq!(layers.beauty.get(2, 3), Colour::default());
+    }
+
+    fn camera() -> Camera {
+        let mut camera = Camera::default();
+        camera.aspect_ratio = 2.;
+        camera.width = 4;
+        camera.samples_per_pixel = 1;
+        camera.max_depth = 1;
+        camera.vfov = 90.;
+        camera.lookfrom = Point::new([0., 0., 1.]);
+        camera.vup = Vector::new([0., 1., 0.]);
+        camera.focus_dist = 1.;
+        camera
+    }
+
+    #[test]
+    fn serve_answers_each_job_with_its_rows() {
+        let world = HittableList::<Sphere>::new();
+        let mut output = Vec::new();
+        serve(&mut camera(), world, "0 1\n1 2\n".as_bytes(), &mut output).unwrap();
+
+        let mut output = output.as_slice();
+        for _ in 0..2 {
+            let rows = read_layers(&mut output).unwrap();
+            assert_eq!((rows.beauty.width, rows.beauty.height), (4, 1));
+        }
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn serve_rejects_malformed_jobs() {
+        let world = HittableList::<Sphere>::new();
+        let error = serve(&mut camera(), world, "0 x\n".as_bytes(), io::sink()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
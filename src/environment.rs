@@ -0,0 +1,86 @@
+use std::{f64::consts::PI, io};
+
+use rand::random;
+
+use crate::{
+    background::{direction_to_spherical, spherical_to_direction, wrap_angle, Background},
+    colour::{luminance, Colour},
+    image::Image,
+    sampling::Distribution2D,
+    Vector,
+};
+
+/// An equirectangular (latitude-longitude) HDR image surrounding the scene, which lights it.
+pub struct EnvironmentMap {
+    image: Image,
+    /// Rotation about the vertical axis, in degrees.
+    rotation: f64,
+    intensity: f64,
+    distribution: Distribution2D,
+}
+
+impl EnvironmentMap {
+    /// Fails if `image` is empty, since it would light nothing.
+    pub fn new(image: Image, rotation: f64, intensity: f64) -> io::Result<Self> {
+        if image.pixels.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Environment maps need at least one pixel",
+            ));
+        }
+
+        // Weight each texel by its brightness and by the solid angle it covers, which shrinks
+        // towards the poles
+        let mut weights = Vec::with_capacity(image.pixels.len());
+        for j in 0..image.height {
+            let sin_theta = (PI * (j as f64 + 0.5) / image.height as f64).sin();
+            for i in 0..image.width {
+                weights.push(luminance(image.get(i, j)) * sin_theta);
+            }
+        }
+        let distribution =
+            Distribution2D::new(&weights, image.width as usize, image.height as usize);
+
+        Ok(Self {
+            image,
+            rotation,
+            intensity,
+            distribution,
+        })
+    }
+
+    /// Maps a direction to texture coordinates in `[0, 1)^2`, with `v` running downwards.
+    fn direction_to_uv(&self, direction: Vector<f64, 3>) -> (f64, f64) {
+        let (phi, theta) = direction_to_spherical(direction.unit());
+        let phi = wrap_angle(phi - self.rotation.to_radians());
+        (phi / (2. * PI), theta / PI)
+    }
+
+    fn uv_to_direction(&self, u: f64, v: f64) -> Vector<f64, 3> {
+        spherical_to_direction(u * 2. * PI + self.rotation.to_radians(), v * PI)
+    }
+}
+
+impl Background for EnvironmentMap {
+    fn colour(&self, direction: Vector<f64, 3>) -> Colour {
+        let (u, v) = self.direction_to_uv(direction);
+        let i = ((u * self.image.width as f64) as u32).min(self.image.width - 1);
+        let j = ((v * self.image.height as f64) as u32).min(self.image.height - 1);
+        self.image.get(i, j) * self.intensity
+    }
+
+    fn sample_direction(&self) -> Option<Vector<f64, 3>> {
+        let ((u, v), _) = self.distribution.sample(random(), random());
+        Some(self.uv_to_direction(u, v))
+    }
+
+    fn pdf(&self, direction: Vector<f64, 3>) -> f64 {
+        let (u, v) = self.direction_to_uv(direction);
+        let sin_theta = (v * PI).sin();
+        if sin_theta <= 0. {
+            return 0.;
+        }
+        // Convert from density over the image to density over solid angle
+        self.distribution.pdf(u, v) / (2. * PI * PI * sin_theta)
+    }
+}
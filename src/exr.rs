@@ -0,0 +1,307 @@
+use std::io::{self, Write};
+
+/// A named channel of `width * height` samples, in row-major order.
+pub struct Channel {
+    pub name: String,
+    pub samples: Vec<f32>,
+}
+
+impl Channel {
+    pub fn new(name: &str, samples: Vec<f32>) -> Self {
+        Self {
+            name: name.to_string(),
+            samples,
+        }
+    }
+}
+
+/// Fails unless the image has pixels, its size fits the file formats, and every channel has a
+/// sample for each pixel.
+fn check_size(width: u32, height: u32, channels: &[Channel]) -> io::Result<()> {
+    let invalid = |message: &str| Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+    if width == 0 || height == 0 {
+        return invalid("Images need at least one pixel");
+    }
+    if i32::try_from(width).is_err() || i32::try_from(height).is_err() {
+        return invalid("Image is too large");
+    }
+    let pixels = width as usize * height as usize;
+    if channels
+        .iter()
+        .any(|channel| channel.samples.len() != pixels)
+    {
+        return invalid("Every channel needs one sample per pixel");
+    }
+    Ok(())
+}
+
+/// Writes an uncompressed, single-part, scanline OpenEXR file holding 32-bit float channels.
+pub fn write_exr(
+    mut out: impl Write,
+    width: u32,
+    height: u32,
+    channels: &[Channel],
+) -> io::Result<()> {
+    check_size(width, height, channels)?;
+
+    // The file format requires channels to be stored in alphabetical order
+    let mut channels: Vec<&Channel> = channels.iter().collect();
+    channels.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut data = Vec::new();
+
+    // Magic number, then version 2 with no flags set (single-part scanline)
+    data.extend_from_slice(&[0x76, 0x2f, 0x31, 0x01]);
+    data.extend_from_slice(&2i32.to_le_bytes());
+
+    let mut chlist = Vec::new();
+    for channel in &channels {
+        chlist.extend_from_slice(channel.name.as_bytes());
+        chlist.push(0);
+        chlist.extend_from_slice(&2i32.to_le_bytes()); // FLOAT
+        chlist.extend_from_slice(&[0, 0, 0, 0]); // pLinear and reserved
+        chlist.extend_from_slice(&1i32.to_le_bytes()); // xSampling
+        chlist.extend_from_slice(&1i32.to_le_bytes()); // ySampling
+    }
+    chlist.push(0);
+
+    let mut window = Vec::new();
+    for v in [0, 0, width as i32 - 1, height as i32 - 1] {
+        window.extend_from_slice(&v.to_le_bytes());
+    }
+
+    attribute(&mut data, "channels", "chlist", &chlist);
+    attribute(&mut data, "compression", "compression", &[0]);
+    attribute(&mut data, "dataWindow", "box2i", &window);
+    attribute(&mut data, "displayWindow", "box2i", &window);
+    attribute(&mut data, "lineOrder", "lineOrder", &[0]);
+    attribute(&mut data, "pixelAspectRatio", "float", &1f32.to_le_bytes());
+    attribute(&mut data, "screenWindowCenter", "v2f", &[0; 8]);
+    attribute(&mut data, "screenWindowWidth", "float", &1f32.to_le_bytes());
+    data.push(0);
+
+    // Offset table, with one entry per scanline
+    let line_size = 8 + (width as usize * 4 * channels.len());
+    let first_line = data.len() + height as usize * 8;
+    for y in 0..height as usize {
+        data.extend_from_slice(&((first_line + y * line_size) as u64).to_le_bytes());
+    }
+
+    for y in 0..height as usize {
+        data.extend_from_slice(&(y as i32).to_le_bytes());
+        data.extend_from_slice(&((line_size - 8) as i32).to_le_bytes());
+        for channel in &channels {
+            let row = &channel.samples[y * width as usize..(y + 1) * width as usize];
+            for sample in row {
+                data.extend_from_slice(&sample.to_le_bytes());
+            }
+        }
+    }
+
+    out.write_all(&data)
+}
+
+fn attribute(data: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    data.extend_from_slice(name.as_bytes());
+    data.push(0);
+    data.extend_from_slice(kind.as_bytes());
+    data.push(0);
+    data.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    data.extend_from_slice(value);
+}
+
+/// Writes channels as a Portable Float Map: one channel as greyscale, or three as colour.
+pub fn write_pfm(
+    mut out: impl Write,
+    width: u32,
+    height: u32,
+    channels: &[Channel],
+) -> io::Result<()> {
+    check_size(width, height, channels)?;
+    let magic = match channels.len() {
+        1 => "Pf",
+        3 => "PF",
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Portable Float Maps hold one or three channels",
+            ))
+        }
+    };
+    // A negative scale marks the data as little-endian
+    out.write_fmt(format_args!("{magic}\n{width} {height}\n-1.0\n"))?;
+
+    let mut data = Vec::new();
+    // Rows are stored bottom-to-top
+    for y in (0..height as usize).rev() {
+        for x in 0..width as usize {
+            for channel in channels {
+                data.extend_from_slice(&channel.samples[y * width as usize + x].to_le_bytes());
+            }
+        }
+    }
+
+    out.write_all(&data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channels() -> Vec<Channel> {
+        vec![
+            Channel::new("G", vec![1., 2., 3., 4., 5., 6.]),
+            Channel::new("B", vec![-1., -2., -3., -4., -5., -6.]),
+        ]
+    }
+
+    fn i32_at(data: &[u8], at: usize) -> i32 {
+        i32::from_le_bytes(data[at..at + 4].try_into().unwrap())
+    }
+
+    fn f32_at(data: &[u8], at: usize) -> f32 {
+        f32::from_le_bytes(data[at..at + 4].try_into().unwrap())
+    }
+
+    /// Reads the header's attributes as `(name, type, value)`, returning them along with the
+    /// offset just past the header.
+    fn attributes(data: &[u8]) -> (Vec<(String, String, Vec<u8>)>, usize) {
+        let read_string = |at: &mut usize| {
+            let end = *at + data[*at..].iter().position(|b| *b == 0).unwrap();
+            let string = String::from_utf8(data[*at..end].to_vec()).unwrap();
+            *at = end + 1;
+            string
+        };
+        let mut at = 8;
+        let mut attributes = Vec::new();
+        while data[at] != 0 {
+            let name = read_string(&mut at);
+            let kind = read_string(&mut at);
+            let size = i32_at(data, at) as usize;
+            attributes.push((name, kind, data[at + 4..at + 4 + size].to_vec()));
+            at += 4 + size;
+        }
+        (attributes, at + 1)
+    }
+
+    #[test]
+    fn exr_header_describes_the_image() {
+        let mut data = Vec::new();
+        write_exr(&mut data, 3, 2, &channels()).unwrap();
+
+        assert_eq!(data[..4], [0x76, 0x2f, 0x31, 0x01]);
+        assert_eq!(i32_at(&data, 4), 2);
+
+        let (attributes, _) = attributes(&data);
+        let names: Vec<&str> = attributes.iter().map(|a| a.0.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "channels",
+                "compression",
+                "dataWindow",
+                "displayWindow",
+                "lineOrder",
+                "pixelAspectRatio",
+                "screenWindowCenter",
+                "screenWindowWidth",
+            ]
+        );
+
+        // Channels are sorted by name, each a FLOAT sampled at every pixel
+        let chlist = &attributes[0].2;
+        let mut expected = Vec::new();
+        for name in [b'B', b'G'] {
+            expected.extend_from_slice(&[name, 0, 2, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0]);
+        }
+        expected.push(0);
+        assert_eq!(*chlist, expected);
+
+        let window = &attributes[2].2;
+        let window: Vec<i32> = (0..4).map(|i| i32_at(window, i * 4)).collect();
+        assert_eq!(window, [0, 0, 2, 1]);
+    }
+
+    #[test]
+    fn exr_scanlines_hold_each_channel_in_turn() {
+        let mut data = Vec::new();
+        write_exr(&mut data, 3, 2, &channels()).unwrap();
+        let (_, table) = attributes(&data);
+
+        // Each scanline is its y coordinate, its size, then a row of each channel
+        let line_size = 8 + 3 * 4 * 2;
+        assert_eq!(data.len(), table + 2 * 8 + 2 * line_size);
+        for y in 0..2 {
+            let offset =
+                u64::from_le_bytes(data[table + y * 8..table + y * 8 + 8].try_into().unwrap());
+            let line = offset as usize;
+            assert_eq!(line, table + 2 * 8 + y * line_size);
+            assert_eq!(i32_at(&data, line), y as i32);
+            assert_eq!(i32_at(&data, line + 4), line_size as i32 - 8);
+
+            let samples: Vec<f32> = (0..6).map(|i| f32_at(&data, line + 8 + i * 4)).collect();
+            let first = 3. * y as f32;
+            assert_eq!(
+                samples,
+                [
+                    -first - 1.,
+                    -first - 2.,
+                    -first - 3.,
+                    first + 1.,
+                    first + 2.,
+                    first + 3.
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn pfm_stores_rows_bottom_to_top() {
+        let mut channels = channels();
+        channels.push(Channel::new("R", vec![0.; 6]));
+        let mut data = Vec::new();
+        write_pfm(&mut data, 3, 2, &channels).unwrap();
+
+        let header = b"PF\n3 2\n-1.0\n";
+        assert_eq!(data[..header.len()], *header);
+        let samples: Vec<f32> = (0..18)
+            .map(|i| f32_at(&data, header.len() + i * 4))
+            .collect();
+        assert_eq!(
+            samples,
+            [4., -4., 0., 5., -5., 0., 6., -6., 0., 1., -1., 0., 2., -2., 0., 3., -3., 0.]
+        );
+    }
+
+    #[test]
+    fn pfm_with_one_channel_is_greyscale() {
+        let mut data = Vec::new();
+        write_pfm(&mut data, 1, 1, &[Channel::new("Z", vec![0.5])]).unwrap();
+        assert_eq!(
+            data,
+            [b"Pf\n1 1\n-1.0\n".as_slice(), &0.5f32.to_le_bytes()].concat()
+        );
+    }
+
+    #[test]
+    fn pfm_needs_one_or_three_channels() {
+        for count in [0, 2, 4] {
+            let channels: Vec<Channel> = (0..count).map(|_| Channel::new("Y", vec![0.5])).collect();
+            let error = write_pfm(Vec::new(), 1, 1, &channels).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+
+    #[test]
+    fn empty_or_mismatched_images_are_rejected() {
+        let empty = [Channel::new("Y", Vec::new())];
+        assert!(write_exr(Vec::new(), 0, 2, &empty).is_err());
+        assert!(write_exr(Vec::new(), 3, 0, &empty).is_err());
+        assert!(write_pfm(Vec::new(), 0, 0, &empty).is_err());
+
+        let short = [Channel::new("Y", vec![1., 2.])];
+        assert!(write_exr(Vec::new(), 3, 2, &short).is_err());
+        assert!(write_pfm(Vec::new(), 3, 2, &short).is_err());
+        assert!(write_exr(Vec::new(), u32::MAX, 1, &short).is_err());
+    }
+}
@@ -1,7 +1,8 @@
 use std::sync::Arc;
 
-use crate::{lambertian::Lambertian, material::Material, ray::Ray, Interval};
+use crate::{aabb::Aabb, lambertian::Lambertian, material::Material, onb::Onb, ray::Ray, Interval};
 use linalg::{vector::Vector, Point};
+use rand::random;
 
 #[derive(Clone)]
 pub struct HitRecord {
@@ -10,6 +11,17 @@ pub struct HitRecord {
     pub material: Arc<dyn Material>,
     pub distance: f64,
     pub front_face: bool,
+    /// Surface coordinates of the hit, for looking up textures.
+    pub u: f64,
+    pub v: f64,
+    /// The ray's time, for materials which change over time.
+    pub time: f64,
+    /// How the hit point moves as `u` and `v` increase, giving the surface's tangent frame.
+    pub dpdu: Vector<f64, 3>,
+    pub dpdv: Vector<f64, 3>,
+    /// Index (starting from 1) of the object hit in the outermost `HittableList`.
+    pub object_id: u32,
+    pub material_id: u32,
 }
 
 impl Default for HitRecord {
@@ -20,6 +32,13 @@ impl Default for HitRecord {
             material: Arc::new(Lambertian::default()),
             distance: 0.0,
             front_face: true,
+            u: 0.0,
+            v: 0.0,
+            time: 0.0,
+            dpdu: Vector::default(),
+            dpdv: Vector::default(),
+            object_id: 0,
+            material_id: 0,
         }
     }
 }
@@ -35,8 +54,51 @@ impl HitRecord {
             -*outward_normal
         };
     }
+
+    pub fn surface(&self) -> SurfacePoint {
+        SurfacePoint {
+            p: self.p,
+            u: self.u,
+            v: self.v,
+            time: self.time,
+            front_face: self.front_face,
+        }
+    }
+
+    /// A unit tangent along increasing `u`, perpendicular to the normal.
+    pub fn tangent(&self) -> Vector<f64, 3> {
+        let tangent = self.dpdu - self.normal * self.dpdu.dot(&self.normal);
+        if tangent.near_zero() {
+            // Degenerate parameterisation, so any tangent will do
+            return Onb::new(self.normal).u;
+        }
+        tangent.unit()
+    }
+}
+
+/// The parts of a hit which decide whether the surface is there at all, which objects work out
+/// before filling in the rest of the `HitRecord`.
+#[derive(Clone, Copy, Debug)]
+pub struct SurfacePoint {
+    pub p: Point<f64, 3>,
+    pub u: f64,
+    pub v: f64,
+    pub time: f64,
+    pub front_face: bool,
+}
+
+impl SurfacePoint {
+    /// Whether `material` is really here, rather than cut away by an alpha mask. Partially
+    /// transparent surfaces are kept at random, in proportion to their opacity.
+    pub fn is_opaque(&self, material: &dyn Material) -> bool {
+        let opacity = material.opacity(self);
+        opacity >= 1. || (opacity > 0. && random::<f64>() < opacity)
+    }
 }
 
 pub trait Hittable: Send + Sync {
     fn hit(&self, ray: &Ray, ray_t: Interval, record: &mut HitRecord) -> bool;
+
+    /// Returns a box enclosing the object at every point in time.
+    fn bounding_box(&self) -> Aabb;
 }
@@ -1,17 +1,30 @@
 use std::sync::Arc;
 
 use crate::{
+    aabb::Aabb,
     hittable::{HitRecord, Hittable},
-    material::Material,
     Interval,
 };
 
-#[derive(Default, Clone)]
-pub struct HittableList<O: Clone + Default + Hittable> {
+pub struct HittableList<O: Hittable + ?Sized> {
     pub objects: Vec<Arc<O>>,
 }
 
-impl<O: Clone + Default + Hittable> HittableList<O> {
+impl<O: Hittable + ?Sized> Default for HittableList<O> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<O: Hittable + ?Sized> Clone for HittableList<O> {
+    fn clone(&self) -> Self {
+        Self {
+            objects: self.objects.clone(),
+        }
+    }
+}
+
+impl<O: Hittable + ?Sized> HittableList<O> {
     pub fn new() -> Self {
         Self {
             objects: Vec::new(),
@@ -33,7 +46,7 @@ impl<O: Clone + Default + Hittable> HittableList<O> {
     }
 }
 
-impl<O: Clone + Default + Material + Hittable> Hittable for HittableList<O> {
+impl<O: Hittable + ?Sized> Hittable for HittableList<O> {
     fn hit(
         &self,
         ray: &crate::ray::Ray,
@@ -44,14 +57,21 @@ impl<O: Clone + Default + Material + Hittable> Hittable for HittableList<O> {
         let mut hit_anything = false;
         let mut closest_so_far = ray_t.max;
 
-        for object in &self.objects {
+        for (index, object) in self.objects.iter().enumerate() {
             if object.hit(ray, Interval::new(ray_t.min, closest_so_far), &mut temp_rec) {
                 hit_anything = true;
                 closest_so_far = temp_rec.distance;
+                temp_rec.object_id = index as u32 + 1;
                 *record = temp_rec.clone();
             }
         }
 
         hit_anything
     }
+
+    fn bounding_box(&self) -> Aabb {
+        self.objects.iter().fold(Aabb::empty(), |bbox, object| {
+            Aabb::union(bbox, object.bounding_box())
+        })
+    }
 }
@@ -0,0 +1,350 @@
+use std::io::{self, BufRead};
+
+use crate::colour::{srgb_to_linear, Colour};
+
+/// A rectangular buffer of linear colour values, stored in row-major order.
+#[derive(Clone, Default)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<Colour>,
+}
+
+impl Image {
+    pub fn new(width: u32, height: u32) -> Self {
+        let size = (width as usize)
+            .checked_mul(height as usize)
+            .expect("Image is too large");
+        Self {
+            width,
+            height,
+            pixels: vec![Colour::zero(); size],
+        }
+    }
+
+    pub fn get(&self, i: u32, j: u32) -> Colour {
+        self.pixels[j as usize * self.width as usize + i as usize]
+    }
+
+    pub fn set(&mut self, i: u32, j: u32, colour: Colour) {
+        self.pixels[j as usize * self.width as usize + i as usize] = colour;
+    }
+
+    pub fn scale(&mut self, factor: f64) {
+        for pixel in self.pixels.iter_mut() {
+            *pixel = *pixel * factor;
+        }
+    }
+
+    /// Bilinearly samples the image at continuous pixel coordinates, where pixel `(i, j)` covers
+    /// `[i, i + 1) x [j, j + 1)`. Coordinates outside the image are clamped to its edge.
+    pub fn sample(&self, x: f64, y: f64) -> Colour {
+        let x = (x - 0.5).clamp(0., (self.width - 1) as f64);
+        let y = (y - 0.5).clamp(0., (self.height - 1) as f64);
+        let (i, j) = (x as u32, y as u32);
+        let (i1, j1) = ((i + 1).min(self.width - 1), (j + 1).min(self.height - 1));
+        let (tx, ty) = (x - i as f64, y - j as f64);
+
+        let top = self.get(i, j) * (1. - tx) + self.get(i1, j) * tx;
+        let bottom = self.get(i, j1) * (1. - tx) + self.get(i1, j1) * tx;
+        top * (1. - ty) + bottom * ty
+    }
+
+    /// Copies `source` into this image, with its top left corner at `(x, y)`.
+    pub fn blit(&mut self, x: u32, y: u32, source: &Image) {
+        for j in 0..source.height {
+            for i in 0..source.width {
+                self.set(x + i, y + j, source.get(i, j));
+            }
+        }
+    }
+
+    /// Copies the rows of `rows`, which must be as wide as this image, into it, starting at row
+    /// `start`.
+    pub fn blit_rows(&mut self, start: u32, rows: &Image) {
+        assert_eq!(rows.width, self.width, "Rows must be as wide as the image");
+        let offset = (start * self.width) as usize;
+        self.pixels[offset..offset + rows.pixels.len()].copy_from_slice(&rows.pixels);
+    }
+
+    /// Reads a Radiance RGBE (`.hdr`) file, uncompressed or run-length encoded.
+    pub fn read_hdr(mut input: impl BufRead) -> io::Result<Image> {
+        let invalid =
+            |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+
+        // Header lines run until a blank line, followed by the resolution line
+        let mut line = String::new();
+        input.read_line(&mut line)?;
+        if !line.starts_with("#?") {
+            return Err(invalid("Not a Radiance HDR file"));
+        }
+        loop {
+            line.clear();
+            if input.read_line(&mut line)? == 0 {
+                return Err(invalid("Truncated HDR header"));
+            }
+            if line.trim().is_empty() {
+                break;
+            }
+            if line.starts_with("FORMAT=") && line.trim() != "FORMAT=32-bit_rle_rgbe" {
+                return Err(invalid("Only RGBE HDR files are supported"));
+            }
+        }
+
+        line.clear();
+        input.read_line(&mut line)?;
+        let resolution: Vec<&str> = line.split_whitespace().collect();
+        let (height, width) = match resolution[..] {
+            ["-Y", h, "+X", w] => (h, w),
+            _ => return Err(invalid("Only -Y +X oriented HDR files are supported")),
+        };
+        let parse = |token: &str| {
+            token
+                .parse::<u32>()
+                .map_err(|_| invalid("Bad HDR resolution"))
+        };
+        let (width, height) = (parse(width)?, parse(height)?);
+        if width == 0 || height == 0 {
+            return Err(invalid("Empty HDR image"));
+        }
+
+        let mut image = Image::new(width, height);
+        let mut scanline = vec![[0u8; 4]; width as usize];
+
+        for j in 0..height {
+            let mut start = [0u8; 4];
+            input.read_exact(&mut start)?;
+
+            let run_length_encoded = (8..0x8000).contains(&width)
+                && start[0] == 2
+                && start[1] == 2
+                && ((start[2] as u32) << 8 | start[3] as u32) == width;
+
+            if run_length_encoded {
+                // Each channel is stored separately, as runs and literal spans
+                for channel in 0..4 {
+                    let mut i = 0;
+                    while i < width as usize {
+                        let mut count = [0u8; 1];
+                        input.read_exact(&mut count)?;
+                        let count = count[0] as usize;
+                        let length = if count > 128 { count - 128 } else { count };
+                        if length == 0 || i + length > width as usize {
+                            return Err(invalid("Bad HDR run length"));
+                        }
+                        if count > 128 {
+                            let mut value = [0u8; 1];
+                            input.read_exact(&mut value)?;
+                            for pixel in scanline.iter_mut().skip(i).take(length) {
+                                pixel[channel] = value[0];
+                            }
+                            i += length;
+                        } else {
+                            let mut values = vec![0u8; count];
+                            input.read_exact(&mut values)?;
+                            for (pixel, value) in scanline.iter_mut().skip(i).zip(values) {
+                                pixel[channel] = value;
+                            }
+                            i += count;
+                        }
+                    }
+                }
+            } else {
+                scanline[0] = start;
+                for pixel in scanline.iter_mut().skip(1) {
+                    input.read_exact(pixel)?;
+                }
+            }
+
+            for (i, [r, g, b, e]) in scanline.iter().enumerate() {
+                let colour = if *e == 0 {
+                    Colour::zero()
+                } else {
+                    let f = 2f64.powi(*e as i32 - (128 + 8));
+                    Colour::new([
+                        (*r as f64 + 0.5) * f,
+                        (*g as f64 + 0.5) * f,
+                        (*b as f64 + 0.5) * f,
+                    ])
+                };
+                image.set(i as u32, j, colour);
+            }
+        }
+
+        Ok(image)
+    }
+
+    /// Reads a binary (P6) or plain (P3) PPM file, converting its sRGB values to linear colour.
+    pub fn read_ppm(mut input: impl BufRead) -> io::Result<Image> {
+        let invalid =
+            |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+
+        // The header is made of four whitespace-separated tokens, and may contain comments
+        let mut header = Vec::new();
+        while header.len() < 4 {
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                return Err(invalid("Truncated PPM header"));
+            }
+            let line = line.split('#').next().unwrap_or("");
+            header.extend(line.split_whitespace().map(str::to_string));
+        }
+
+        let parse = |token: &str| token.parse::<u32>().map_err(|_| invalid("Bad PPM header"));
+        let width = parse(&header[1])?;
+        let height = parse(&header[2])?;
+        let max_value = parse(&header[3])?;
+        if max_value == 0 || max_value > 65535 {
+            return Err(invalid("Bad PPM maximum value"));
+        }
+        let length = (width as usize)
+            .checked_mul(height as usize)
+            .and_then(|pixels| pixels.checked_mul(3))
+            .ok_or_else(|| invalid("PPM image is too large"))?;
+
+        let values: Vec<u32> = match header[0].as_str() {
+            "P6" => {
+                let mut data = Vec::new();
+                input.read_to_end(&mut data)?;
+                if max_value < 256 {
+                    data.into_iter().map(u32::from).collect()
+                } else {
+                    // A trailing odd byte is only half a value, so is left for the length check
+                    data.chunks_exact(2)
+                        .map(|b| u32::from(b[0]) << 8 | u32::from(b[1]))
+                        .collect()
+                }
+            }
+            "P3" => {
+                let mut data = String::new();
+                input.read_to_string(&mut data)?;
+                data.split_whitespace()
+                    .map(parse)
+                    .collect::<io::Result<Vec<u32>>>()?
+            }
+            _ => return Err(invalid("Only P3 and P6 PPM files are supported")),
+        };
+
+        if values.len() < length {
+            return Err(invalid("Truncated PPM data"));
+        }
+
+        let max_value = max_value as f64;
+        let mut image = Image::new(width, height);
+        for (pixel, rgb) in image.pixels.iter_mut().zip(values.chunks(3)) {
+            *pixel = Colour::new([
+                srgb_to_linear(rgb[0] as f64 / max_value),
+                srgb_to_linear(rgb[1] as f64 / max_value),
+                srgb_to_linear(rgb[2] as f64 / max_value),
+            ]);
+        }
+        Ok(image)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hdr(width: u32, pixels: &[u8]) -> Vec<u8> {
+        let header = format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X {width}\n");
+        [header.as_bytes(), pixels].concat()
+    }
+
+    /// An 8 pixel run-length encoded scanline with `red` as the red channel's encoding.
+    fn rle(red: &[u8]) -> Vec<u8> {
+        let mut line = vec![2, 2, 0, 8];
+        line.extend_from_slice(red);
+        line.extend_from_slice(&[8, 0, 1, 2, 3, 4, 5, 6, 7]); // green, as a literal span
+        line.extend_from_slice(&[128 + 4, 10, 128 + 4, 20]); // blue, as two runs
+        line.extend_from_slice(&[128 + 8, 129]); // exponent
+        line
+    }
+
+    #[test]
+    fn reads_run_length_encoded_scanlines() {
+        let image = Image::read_hdr(hdr(8, &rle(&[128 + 8, 127])).as_slice()).unwrap();
+        assert_eq!((image.width, image.height), (8, 1));
+        for i in 0..8 {
+            let expected = [127.5, i as f64 + 0.5, if i < 4 { 10.5 } else { 20.5 }];
+            assert_eq!(image.get(i, 0), Colour::new(expected) / 128.);
+        }
+    }
+
+    #[test]
+    fn reads_flat_scanlines() {
+        let image = Image::read_hdr(hdr(2, &[1, 2, 3, 136, 0, 0, 0, 0]).as_slice()).unwrap();
+        assert_eq!(image.get(0, 0), Colour::new([1.5, 2.5, 3.5]));
+        assert_eq!(image.get(1, 0), Colour::zero());
+    }
+
+    #[test]
+    fn rejects_empty_images() {
+        let header = b"#?RADIANCE\n\n-Y 0 +X 8\n";
+        let error = Image::read_hdr(header.as_slice()).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rejects_bad_run_lengths() {
+        for red in [
+            &[0, 1][..],
+            &[128, 1],
+            &[128 + 9, 1],
+            &[4, 1, 2, 3, 4, 5, 1, 2, 3, 4, 5],
+        ] {
+            let error = Image::read_hdr(hdr(8, &rle(red)).as_slice()).err().unwrap();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{red:?}");
+        }
+    }
+
+    fn ppm(header: &str, data: &[u8]) -> io::Result<Image> {
+        Image::read_ppm([header.as_bytes(), data].concat().as_slice())
+    }
+
+    #[test]
+    fn reads_plain_ppms() {
+        let image = ppm("P3\n# A comment\n2 1\n255\n", b"0 255 0\n255 255 255\n").unwrap();
+        assert_eq!((image.width, image.height), (2, 1));
+        assert_eq!(image.get(0, 0), Colour::new([0., 1., 0.]));
+        assert_eq!(image.get(1, 0), Colour::new([1., 1., 1.]));
+    }
+
+    #[test]
+    fn reads_8_bit_binary_ppms() {
+        let image = ppm("P6\n1 2\n255\n", &[255, 0, 0, 0, 0, 255]).unwrap();
+        assert_eq!(image.get(0, 0), Colour::new([1., 0., 0.]));
+        assert_eq!(image.get(0, 1), Colour::new([0., 0., 1.]));
+    }
+
+    #[test]
+    fn reads_16_bit_binary_ppms() {
+        let image = ppm("P6\n1 1\n65535\n", &[0xff, 0xff, 0, 0, 0xff, 0xff]).unwrap();
+        assert_eq!(image.get(0, 0), Colour::new([1., 0., 1.]));
+    }
+
+    #[test]
+    fn rejects_truncated_ppms() {
+        let truncated = [
+            ppm("P6\n2 1\n255\n", &[1, 2, 3, 4, 5]),
+            ppm("P6\n1 1\n65535\n", &[1, 2, 3, 4, 5]),
+            ppm("P3\n1 1\n255\n", b"1 2"),
+            ppm("P6\n1 1\n", &[]),
+        ];
+        for result in truncated {
+            assert_eq!(result.err().unwrap().kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_ppm_headers() {
+        let bad = [
+            ppm("P6\n4294967295 4294967295\n255\n", &[]),
+            ppm("P6\n1 1\n0\n", &[0, 0, 0]),
+            ppm("P5\n1 1\n255\n", &[0]),
+        ];
+        for result in bad {
+            assert_eq!(result.err().unwrap().kind(), io::ErrorKind::InvalidData);
+        }
+    }
+}
@@ -1,4 +1,4 @@
-#[derive(Default, Clone, Copy)]
+#[derive(Default, Clone, Copy, Debug)]
 pub struct Interval {
     pub min: f64,
     pub max: f64,
@@ -9,6 +9,24 @@ impl Interval {
         Interval { min, max }
     }
 
+    /// Returns the smallest interval containing both `a` and `b`.
+    pub fn union(a: Interval, b: Interval) -> Self {
+        Interval {
+            min: a.min.min(b.min),
+            max: a.max.max(b.max),
+        }
+    }
+
+    pub fn size(&self) -> f64 {
+        self.max - self.min
+    }
+
+    /// Returns the interval padded by `delta / 2` at either end.
+    pub fn expand(&self, delta: f64) -> Self {
+        let padding = delta / 2.;
+        Interval::new(self.min - padding, self.max + padding)
+    }
+
     pub fn contains(&self, x: f64) -> bool {
         self.min <= x && x <= self.max
     }
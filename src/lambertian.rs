@@ -1,6 +1,13 @@
+use std::f64::consts::PI;
+
 use linalg::vector::Vector;
 
-use crate::{colour::Colour, hittable::HitRecord, material::Material, Ray};
+use crate::{
+    colour::Colour,
+    hittable::HitRecord,
+    material::{Material, ScatterSample},
+    Ray,
+};
 
 #[derive(Clone, Copy, Default, Debug)]
 pub struct Lambertian {
@@ -14,22 +21,32 @@ impl Lambertian {
 }
 
 impl Material for Lambertian {
-    fn scatter(
-        &self,
-        ray_in: &Ray,
-        record: &HitRecord,
-        attenuation: &mut Colour,
-        scattered: &mut Ray,
-    ) -> bool {
+    fn sample(&self, ray_in: &Ray, record: &HitRecord) -> Option<ScatterSample> {
         let mut scatter_direction = record.normal + Vector::random_unit_vector();
 
         if scatter_direction.near_zero() {
             scatter_direction = record.normal;
         }
 
-        *scattered = Ray::new(record.p, scatter_direction, Some(ray_in.time()));
-        *attenuation = self.albedo;
+        let direction = scatter_direction.unit();
+        Some(ScatterSample {
+            direction,
+            weight: self.albedo,
+            pdf: self.pdf(ray_in, record, direction),
+            delta: false,
+        })
+    }
 
-        true
+    fn eval(&self, ray_in: &Ray, record: &HitRecord, direction: Vector<f64, 3>) -> Colour {
+        self.albedo * self.pdf(ray_in, record, direction)
+    }
+
+    fn pdf(&self, _ray_in: &Ray, record: &HitRecord, direction: Vector<f64, 3>) -> f64 {
+        let cos_theta = record.normal.dot(&direction);
+        if cos_theta < 0. {
+            0.
+        } else {
+            cos_theta / PI
+        }
     }
 }
@@ -0,0 +1,184 @@
+use std::{f64::consts::PI, io, sync::Arc};
+
+use rand::random;
+
+use crate::{colour::luminance, image::Image, sampling::Distribution1D, Vector};
+
+/// A physical description of a camera's lens and sensor. Setting one on a `Camera` derives its
+/// `vfov` and `defocus_angle`, so artists can think in millimetres and f-stops.
+#[derive(Clone, Copy, Debug)]
+pub struct Lens {
+    /// Focal length, in millimetres.
+    pub focal_length: f64,
+    /// Width of the sensor (or film gate), in millimetres, e.g. 36 for full frame.
+    pub sensor_width: f64,
+    pub f_stop: f64,
+    /// How many scene units make up a metre.
+    pub units_per_metre: f64,
+}
+
+impl Lens {
+    pub fn new(focal_length: f64, sensor_width: f64, f_stop: f64) -> Self {
+        Self {
+            focal_length,
+            sensor_width,
+            f_stop,
+            units_per_metre: 1.0,
+        }
+    }
+
+    /// The vertical field of view, in degrees, for an image of the given aspect ratio.
+    pub fn vfov(&self, aspect_ratio: f64) -> f64 {
+        let sensor_height = self.sensor_width / aspect_ratio;
+        2. * (sensor_height / (2. * self.focal_length))
+            .atan()
+            .to_degrees()
+    }
+
+    /// The radius of the entrance pupil, in scene units.
+    pub fn aperture_radius(&self) -> f64 {
+        let diameter = self.focal_length / self.f_stop;
+        diameter / 2. / 1000. * self.units_per_metre
+    }
+
+    /// The defocus cone angle, in degrees, for a lens focused at `focus_dist`.
+    pub fn defocus_angle(&self, focus_dist: f64) -> f64 {
+        2. * (self.aperture_radius() / focus_dist).atan().to_degrees()
+    }
+}
+
+/// The shape of the camera's aperture, which gives out-of-focus highlights (bokeh) their shape.
+#[derive(Clone, Default)]
+pub enum Aperture {
+    #[default]
+    Circle,
+    /// A regular polygon formed by `blades` straight diaphragm blades, rotated by `rotation`
+    /// degrees.
+    Polygon { blades: u32, rotation: f64 },
+    /// An arbitrary shape, taken from an image.
+    Image(Arc<BokehImage>),
+}
+
+impl Aperture {
+    /// Returns a random point on the aperture, scaled so that a circular aperture fills the unit
+    /// disk.
+    pub fn sample(&self) -> Vector<f64, 3> {
+        match self {
+            Aperture::Circle => Vector::random_in_unit_disk(),
+            Aperture::Polygon { blades, rotation } => {
+                let blades = (*blades).max(3);
+                // Pick one of the triangles fanning out from the centre, then a point inside it
+                let blade = (random::<f64>() * blades as f64) as u32 % blades;
+                let step = 2. * PI / blades as f64;
+                let angle = rotation.to_radians() + blade as f64 * step;
+                let a = Vector::new([angle.cos(), angle.sin(), 0.]);
+                let b = Vector::new([(angle + step).cos(), (angle + step).sin(), 0.]);
+
+                let (mut s, mut t) = (random::<f64>(), random::<f64>());
+                if s + t > 1. {
+                    (s, t) = (1. - s, 1. - t);
+                }
+                a * s + b * t
+            }
+            Aperture::Image(image) => image.sample(),
+        }
+    }
+}
+
+/// An aperture mask, sampled in proportion to each pixel's brightness.
+pub struct BokehImage {
+    width: u32,
+    height: u32,
+    distribution: Distribution1D,
+}
+
+impl BokehImage {
+    /// Fails if the image has no pixels or no bright ones, leaving nothing to sample.
+    pub fn new(image: &Image) -> io::Result<Self> {
+        let weights: Vec<f64> = image.pixels.iter().map(|p| luminance(*p).max(0.)).collect();
+        let total: f64 = weights.iter().sum();
+        if weights.is_empty() || !(total > 0. && total.is_finite()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Bokeh images need at least one bright pixel",
+            ));
+        }
+        Ok(Self {
+            width: image.width,
+            height: image.height,
+            distribution: Distribution1D::new(weights),
+        })
+    }
+
+    fn sample(&self) -> Vector<f64, 3> {
+        let (index, _) = self.distribution.sample_discrete(random());
+        let i = (index as u32 % self.width) as f64 + random::<f64>();
+        let j = (index as u32 / self.width) as f64 + random::<f64>();
+
+        // Map the circle inscribed in the image onto the unit disk
+        let size = self.width.max(self.height) as f64;
+        let x = (2. * i - self.width as f64) / size;
+        let y = (self.height as f64 - 2. * j) / size;
+        Vector::new([x, y, 0.])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colour::Colour;
+
+    const SAMPLES: usize = 10_000;
+
+    #[test]
+    fn apertures_stay_within_the_unit_disk() {
+        let apertures = [
+            Aperture::Circle,
+            Aperture::Polygon {
+                blades: 6,
+                rotation: 15.,
+            },
+        ];
+        for aperture in apertures {
+            for _ in 0..SAMPLES {
+                let p = aperture.sample();
+                assert!(p.length_squared() <= 1. + 1e-12);
+                assert_eq!(p.z(), 0.);
+            }
+        }
+    }
+
+    #[test]
+    fn bladed_apertures_cut_off_the_disk_between_their_corners() {
+        // A square with corners on the axes never reaches past |x| + |y| = 1
+        let aperture = Aperture::Polygon {
+            blades: 4,
+            rotation: 0.,
+        };
+        for _ in 0..SAMPLES {
+            let p = aperture.sample();
+            assert!(p.x().abs() + p.y().abs() <= 1. + 1e-12, "{p:?}");
+        }
+    }
+
+    #[test]
+    fn image_apertures_only_sample_bright_pixels() {
+        // A plus sign: the corners of the image are black
+        let mut image = Image::new(3, 3);
+        for (i, j) in [(1, 0), (0, 1), (1, 1), (2, 1), (1, 2)] {
+            image.set(i, j, Colour::new([1., 1., 1.]));
+        }
+        let aperture = Aperture::Image(Arc::new(BokehImage::new(&image).unwrap()));
+        for _ in 0..SAMPLES {
+            let p = aperture.sample();
+            assert!(p.x().abs() <= 1. && p.y().abs() <= 1.);
+            assert!(p.x().abs() <= 1. / 3. || p.y().abs() <= 1. / 3., "{p:?}");
+        }
+    }
+
+    #[test]
+    fn bokeh_images_need_a_bright_pixel() {
+        assert!(BokehImage::new(&Image::new(0, 0)).is_err());
+        assert!(BokehImage::new(&Image::new(4, 4)).is_err());
+    }
+}
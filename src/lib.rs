@@ -1,14 +1,47 @@
+pub mod aabb;
+pub mod alpha;
+pub mod animation;
+pub mod aov;
+pub mod background;
+pub mod bump;
+pub mod bvh;
 pub mod camera;
 pub mod colour;
+pub mod denoise;
 pub mod dielectric;
+pub mod distributed;
+pub mod environment;
+pub mod exr;
 pub mod hittable;
 pub mod hittable_list;
+pub mod image;
 pub mod interval;
 pub mod lambertian;
+pub mod lens;
+pub mod light;
 pub mod material;
+pub mod medium;
+pub mod mesh;
 pub mod metals;
+pub mod microfacet;
+pub mod mix;
+pub mod onb;
+pub mod png;
+pub mod post;
+pub mod principled;
+pub mod projection;
 pub mod ray;
+pub mod sampling;
+pub mod sequence;
+pub mod shutter;
+pub mod sided;
+pub mod sky;
+pub mod spectrum;
 pub mod sphere;
+pub mod stereo;
+pub mod subsurface;
+pub mod texture;
+pub mod tonemap;
 
 use std::f64::consts::PI;
 
@@ -0,0 +1,230 @@
+use linalg::Point;
+
+use crate::{colour::Colour, Vector};
+
+/// Points closer than this to a point or spot light have no direction to it, so get no light.
+const MIN_DISTANCE_SQUARED: f64 = 1e-12;
+
+/// Light arriving at a point from a light source.
+#[derive(Clone, Copy, Debug)]
+pub struct LightSample {
+    /// Unit direction from the point towards the light.
+    pub direction: Vector<f64, 3>,
+    /// Distance to the light, or infinity if it is infinitely far away.
+    pub distance: f64,
+    /// Irradiance on a surface facing the light.
+    pub irradiance: Colour,
+}
+
+/// A light which isn't part of the scene's geometry, so can't be seen or hit, and is instead
+/// sampled with a shadow ray from every surface a ray hits.
+pub trait Light: Send + Sync {
+    /// Returns the light reaching `p`, ignoring anything in the way, or `None` if none does.
+    fn sample(&self, p: Point<f64, 3>) -> Option<LightSample>;
+}
+
+/// Light shining equally in every direction from a single point.
+#[derive(Clone, Copy, Debug)]
+pub struct PointLight {
+    pub position: Point<f64, 3>,
+    /// Radiant intensity: the irradiance cast at a distance of one unit.
+    pub intensity: Colour,
+}
+
+impl PointLight {
+    pub fn new(position: Point<f64, 3>, intensity: Colour) -> Self {
+        Self {
+            position,
+            intensity,
+        }
+    }
+}
+
+impl Light for PointLight {
+    fn sample(&self, p: Point<f64, 3>) -> Option<LightSample> {
+        let to_light = self.position - p;
+        let distance_squared = to_light.length_squared();
+        if distance_squared <= MIN_DISTANCE_SQUARED {
+            return None;
+        }
+        let distance = distance_squared.sqrt();
+        Some(LightSample {
+            direction: to_light / distance,
+            distance,
+            irradiance: self.intensity / distance_squared,
+        })
+    }
+}
+
+/// A point light restricted to a cone, fading out towards its edge.
+#[derive(Clone, Copy, Debug)]
+pub struct SpotLight {
+    pub position: Point<f64, 3>,
+    /// The direction the light points in.
+    pub direction: Vector<f64, 3>,
+    /// Radiant intensity along the centre of the cone.
+    pub intensity: Colour,
+    /// Angle, in degrees, from the centre of the cone to its edge.
+    pub cone_angle: f64,
+    /// Angle, in degrees, from the centre at which the light starts to fade.
+    pub falloff_start: f64,
+}
+
+impl SpotLight {
+    pub fn new(
+        position: Point<f64, 3>,
+        direction: Vector<f64, 3>,
+        intensity: Colour,
+        cone_angle: f64,
+        falloff_start: f64,
+    ) -> Self {
+        Self {
+            position,
+            direction,
+            intensity,
+            cone_angle,
+            falloff_start,
+        }
+    }
+
+    /// How much of the light's intensity is cast at `cos_theta` from its centre.
+    fn falloff(&self, cos_theta: f64) -> f64 {
+        let cos_edge = self.cone_angle.to_radians().cos();
+        let cos_start = self.falloff_start.min(self.cone_angle).to_radians().cos();
+        if cos_theta <= cos_edge {
+            return 0.;
+        }
+        if cos_theta >= cos_start {
+            return 1.;
+        }
+        // Smoothstep between the edge and the start of the falloff
+        let t = (cos_theta - cos_edge) / (cos_start - cos_edge);
+        t * t * (3. - 2. * t)
+    }
+}
+
+impl Light for SpotLight {
+    fn sample(&self, p: Point<f64, 3>) -> Option<LightSample> {
+        let to_light = self.position - p;
+        let distance_squared = to_light.length_squared();
+        if distance_squared <= MIN_DISTANCE_SQUARED {
+            return None;
+        }
+        let distance = distance_squared.sqrt();
+        let direction = to_light / distance;
+
+        let falloff = self.falloff(-direction.dot(&self.direction.unit()));
+        if falloff == 0. {
+            return None;
+        }
+        Some(LightSample {
+            direction,
+            distance,
+            irradiance: self.intensity * (falloff / distance_squared),
+        })
+    }
+}
+
+/// Parallel light from infinitely far away, like the sun.
+#[derive(Clone, Copy, Debug)]
+pub struct DirectionalLight {
+    /// The direction the light travels in.
+    pub direction: Vector<f64, 3>,
+    pub irradiance: Colour,
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Vector<f64, 3>, irradiance: Colour) -> Self {
+        Self {
+            direction,
+            irradiance,
+        }
+    }
+}
+
+impl Light for DirectionalLight {
+    fn sample(&self, _p: Point<f64, 3>) -> Option<LightSample> {
+        Some(LightSample {
+            direction: -self.direction.unit(),
+            distance: f64::INFINITY,
+            irradiance: self.irradiance,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn point_lights_fall_off_with_the_square_of_distance() {
+        let light = PointLight::new(Point::new([0., 0., 0.]), Colour::new([4., 4., 4.]));
+        let near = light.sample(Point::new([0., 1., 0.])).unwrap();
+        let far = light.sample(Point::new([0., 2., 0.])).unwrap();
+        assert!((near.irradiance.x() - 4.).abs() < 1e-12);
+        assert!((far.irradiance.x() - 1.).abs() < 1e-12);
+        assert!((far.distance - 2.).abs() < 1e-12);
+        assert!((far.direction - Vector::new([0., -1., 0.])).length() < 1e-12);
+    }
+
+    #[test]
+    fn points_at_the_light_get_no_light() {
+        let position = Point::new([1., 2., 3.]);
+        let point = PointLight::new(position, Colour::new([1., 1., 1.]));
+        let spot = SpotLight::new(
+            position,
+            Vector::new([0., -1., 0.]),
+            Colour::new([1., 1., 1.]),
+            30.,
+            20.,
+        );
+        assert!(point.sample(position).is_none());
+        assert!(spot.sample(position).is_none());
+    }
+
+    #[test]
+    fn spot_lights_fade_between_the_falloff_start_and_the_edge() {
+        let light = SpotLight::new(
+            Point::new([0., 0., 0.]),
+            Vector::new([0., -1., 0.]),
+            Colour::new([1., 1., 1.]),
+            30.,
+            20.,
+        );
+        // A point one unit from the light, `degrees` away from the centre of the cone
+        let at = |degrees: f64| {
+            let angle = degrees.to_radians();
+            light.sample(Point::new([angle.sin(), -angle.cos(), 0.]))
+        };
+
+        for degrees in [0., 10., 19.9] {
+            let irradiance = at(degrees).unwrap().irradiance.x();
+            assert!((irradiance - 1.).abs() < 1e-9, "{degrees}: {irradiance}");
+        }
+        let mut previous = 1.;
+        for degrees in [21., 24., 27., 29.] {
+            let irradiance = at(degrees).unwrap().irradiance.x();
+            assert!(
+                irradiance > 0. && irradiance < previous,
+                "{degrees}: {irradiance}"
+            );
+            previous = irradiance;
+        }
+        for degrees in [30.1, 45., 90., 180.] {
+            assert!(at(degrees).is_none(), "{degrees}");
+        }
+    }
+
+    #[test]
+    fn spot_lights_fall_off_with_the_square_of_distance() {
+        let light = SpotLight::new(
+            Point::new([0., 0., 0.]),
+            Vector::new([0., 0., 1.]),
+            Colour::new([9., 9., 9.]),
+            30.,
+            20.,
+        );
+        let irradiance = light.sample(Point::new([0., 0., 3.])).unwrap().irradiance;
+        assert!((irradiance.x() - 1.).abs() < 1e-12);
+    }
+}
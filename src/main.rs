@@ -1,10 +1,39 @@
 use linalg::Point;
-use rand::random;
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use raytracer::{
-    camera::Camera, colour::Colour, dielectric::Dielectric, hittable_list::HittableList,
-    lambertian::Lambertian, metals::Metal, sphere::Sphere, Vector,
+    animation::{Animated, AnimatedMaterial, Interpolation, Keyframe, Track, Transform},
+    aov::AovOutput,
+    bvh::BvhNode,
+    camera::{Camera, CropWindow},
+    colour::Colour,
+    dielectric::Dielectric,
+    distributed::{self, Coordinator},
+    environment::EnvironmentMap,
+    hittable::Hittable,
+    hittable_list::HittableList,
+    image::Image,
+    lambertian::Lambertian,
+    material::{Material, MaterialIds},
+    mesh::{Mesh, MeshData},
+    metals::Metal,
+    sequence::Sequence,
+    sky::Sky,
+    sphere::Sphere,
+    texture::ImageTexture,
+    tonemap::ToneMap,
+    Vector,
 };
-use std::{env, fs::OpenOptions, sync::Arc};
+use std::{
+    env,
+    fs::{File, OpenOptions},
+    io::{self, BufReader, Write},
+    path::PathBuf,
+    process,
+    sync::Arc,
+};
+
+// The scene is generated from a fixed seed so that every worker process builds the same one.
+const SCENE_SEED: u64 = 0;
 
 fn setup_camera() -> Camera {
     let mut camera = Camera::default();
@@ -22,85 +51,254 @@ fn setup_camera() -> Camera {
     camera.defocus_angle = 0.6;
     camera.focus_dist = 10.;
 
+    camera.exposure = 0.;
+
     camera
 }
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
+fn random_colour(rng: &mut StdRng) -> Colour {
+    Colour::new([rng.gen(), rng.gen(), rng.gen()])
+}
 
-    let file = OpenOptions::new()
-        .create(true)
-        .truncate(true)
-        .write(true)
-        .open(args[1].clone())
-        .unwrap();
+/// Loads the mesh given by `--mesh <file.obj>`, if any. `--subdivide <n>` splits each triangle
+/// into four `n` times, and `--displace <height.ppm> <scale>` then raises the surface by the
+/// height map.
+fn load_mesh(args: &[String]) -> Option<MeshData> {
+    let path = &args[args.iter().position(|a| a == "--mesh")? + 1];
+    let mut mesh = MeshData::load_obj(path).expect("Could not read mesh");
 
-    let mut world = HittableList::default();
+    if let Some(subdivide) = args.iter().position(|a| a == "--subdivide") {
+        let times: u32 = args[subdivide + 1]
+            .parse()
+            .expect("--subdivide expects a number of times");
+        for _ in 0..times {
+            mesh.subdivide();
+        }
+    }
 
-    let material_ground = Arc::new(Lambertian::new(Colour::new([0.5, 0.5, 0.5])));
-    world.add(Arc::new(Sphere::new(
-        Point::new([0., -1000., 0.]),
-        None,
-        1000.,
-        material_ground,
-    )));
+    if let Some(displace) = args.iter().position(|a| a == "--displace") {
+        let file = File::open(&args[displace + 1]).expect("Could not open height map");
+        let height = Image::read_ppm(BufReader::new(file)).expect("Could not read height map");
+        let scale: f64 = args[displace + 2]
+            .parse()
+            .expect("--displace expects a height map and a scale");
+        mesh.displace(&ImageTexture::new(height), scale);
+    }
+
+    Some(mesh)
+}
+
+/// Builds the scene, with `mesh` added if there is one. If `animated`, the glass sphere bounces
+/// and the brown one blushes red over time 0 to 1, for rendering with `--frames`.
+fn setup_world(animated: bool, mesh: Option<MeshData>) -> HittableList<dyn Hittable> {
+    let mut rng = StdRng::seed_from_u64(SCENE_SEED);
+    let mut world: HittableList<dyn Hittable> = HittableList::default();
+    let mut materials = MaterialIds::new();
+
+    let material_ground: Arc<dyn Material> =
+        Arc::new(Lambertian::new(Colour::new([0.5, 0.5, 0.5])));
+    let id = materials.id(&material_ground);
+    world.add(Arc::new(
+        Sphere::new(Point::new([0., -1000., 0.]), None, 1000., material_ground)
+            .with_material_id(id),
+    ));
 
     for a in -11..11 {
         for b in -11..11 {
-            let choose_mat = random::<f64>();
+            let choose_mat = rng.gen::<f64>();
             let centre = Point::new([
-                a as f64 + 0.9 * random::<f64>(),
+                a as f64 + 0.9 * rng.gen::<f64>(),
                 0.2,
-                b as f64 + 0.9 * random::<f64>(),
+                b as f64 + 0.9 * rng.gen::<f64>(),
             ]);
 
             if (centre - Point::new([4., 0.2, 0.])).length() > 0.9 {
-                if choose_mat < 0.8 {
+                let (mat, centre2): (Arc<dyn Material>, _) = if choose_mat < 0.8 {
                     // diffuse
-                    let albedo = Colour::random().hadamard(Colour::random());
-                    let mat = Arc::new(Lambertian::new(albedo));
-                    let centre2 = centre + Vector::new([0., random::<f64>() * 0.5, 0.]);
-                    world.add(Arc::new(Sphere::new(centre, Some(centre2), 0.2, mat)));
+                    let albedo = random_colour(&mut rng).hadamard(random_colour(&mut rng));
+                    let centre2 = centre + Vector::new([0., rng.gen::<f64>() * 0.5, 0.]);
+                    (Arc::new(Lambertian::new(albedo)), Some(centre2))
                 } else if choose_mat < 0.95 {
                     // metal
-                    let albedo = Colour::random_range(0.5, 1.);
-                    let fuzz = random::<f64>() * 0.5;
-                    let mat = Arc::new(Metal::new(albedo, fuzz));
-                    world.add(Arc::new(Sphere::new(centre, None, 0.2, mat)));
+                    let albedo = random_colour(&mut rng) * 0.5 + Colour::new([0.5, 0.5, 0.5]);
+                    let fuzz = rng.gen::<f64>() * 0.5;
+                    (Arc::new(Metal::new(albedo, fuzz)), None)
                 } else {
                     // glass
-                    let mat = Arc::new(Dielectric::new(1.5));
-                    world.add(Arc::new(Sphere::new(centre, None, 0.2, mat)));
-                }
+                    (Arc::new(Dielectric::new(1.5)), None)
+                };
+                let id = materials.id(&mat);
+                world.add(Arc::new(
+                    Sphere::new(centre, centre2, 0.2, mat).with_material_id(id),
+                ));
             }
         }
     }
 
-    let material1 = Arc::new(Dielectric::new(1.5));
-    world.add(Arc::new(Sphere::new(
-        Point::new([0., 1., 0.]),
-        None,
-        1.0,
-        material1,
-    )));
-
-    let material2 = Arc::new(Lambertian::new(Colour::new([0.4, 0.2, 0.1])));
-    world.add(Arc::new(Sphere::new(
-        Point::new([-4., 1., 0.]),
-        None,
-        1.0,
-        material2,
-    )));
-
-    let material3 = Arc::new(Metal::new(Colour::new([0.7, 0.6, 0.5]), 0.));
-    world.add(Arc::new(Sphere::new(
-        Point::new([4., 1., 0.]),
-        None,
-        1.,
-        material3,
-    )));
+    let material1: Arc<dyn Material> = Arc::new(Dielectric::new(1.5));
+    let mut material2: Arc<dyn Material> = Arc::new(Lambertian::new(Colour::new([0.4, 0.2, 0.1])));
+    if animated {
+        let red = Arc::new(Lambertian::new(Colour::new([0.7, 0.1, 0.1])));
+        let weight = Track::new(vec![
+            Keyframe::new(0., 0., Interpolation::EASE),
+            Keyframe::new(1., 1., Interpolation::Linear),
+        ]);
+        material2 = Arc::new(AnimatedMaterial::new(material2, red, weight));
+    }
+    let material3: Arc<dyn Material> = Arc::new(Metal::new(Colour::new([0.7, 0.6, 0.5]), 0.));
+    for (centre, material) in [
+        (Point::new([0., 1., 0.]), material1),
+        (Point::new([-4., 1., 0.]), material2),
+        (Point::new([4., 1., 0.]), material3),
+    ] {
+        let id = materials.id(&material);
+        world.add(Arc::new(
+            Sphere::new(centre, None, 1.0, material).with_material_id(id),
+        ));
+    }
+
+    if animated {
+        // Bounce the glass sphere, the first of the three big ones, up and back down
+        let glass = world.objects.len() - 3;
+        let up = Vector::new([0., 1., 0.]);
+        let transform = Transform {
+            translation: Track::new(vec![
+                Keyframe::new(0., up * 0., Interpolation::EASE),
+                Keyframe::new(0.5, up, Interpolation::EASE),
+                Keyframe::new(1., up * 0., Interpolation::Linear),
+            ]),
+            ..Default::default()
+        };
+        world.objects[glass] = Arc::new(Animated::new(world.objects[glass].clone(), transform));
+    }
+
+    if let Some(mesh) = mesh {
+        let material: Arc<dyn Material> = Arc::new(Lambertian::new(Colour::new([0.7, 0.7, 0.7])));
+        let id = materials.id(&material);
+        world.add(Arc::new(Mesh::new(mesh, material, id)));
+    }
+
+    world
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
 
+    let animated = args.iter().any(|a| a == "--frames");
+    let world = BvhNode::new(setup_world(animated, load_mesh(&args)).objects);
     let mut camera = setup_camera();
 
+    if let Some(environment) = args.iter().position(|a| a == "--environment") {
+        let file = File::open(&args[environment + 1]).expect("Could not open environment map");
+        let image = Image::read_hdr(BufReader::new(file)).expect("Could not read environment map");
+        let environment =
+            EnvironmentMap::new(image, 0., 1.).expect("Could not read environment map");
+        camera.background = Some(Arc::new(environment));
+    }
+
+    if let Some(sky) = args.iter().position(|a| a == "--sky") {
+        let parameter = |n: usize| -> f64 {
+            args[sky + n]
+                .parse()
+                .expect("--sky expects sun elevation, sun azimuth and turbidity")
+        };
+        camera.background = Some(Arc::new(Sky::new(parameter(1), parameter(2), parameter(3))));
+    }
+
+    camera.spectral = args.iter().any(|a| a == "--spectral");
+
+    if let Some(tone_map) = args.iter().position(|a| a == "--tone-map") {
+        camera.tone_map = match args.get(tone_map + 1).map(String::as_str) {
+            Some("clamp") => ToneMap::Clamp,
+            Some("reinhard") => ToneMap::Reinhard,
+            Some("aces") => ToneMap::Aces,
+            Some("hable") => ToneMap::Hable,
+            _ => {
+                let _ = io::stderr()
+                    .write(b"--tone-map expects one of clamp, reinhard, aces or hable\n");
+                process::exit(2);
+            }
+        };
+    }
+
+    if let Some(crop) = args.iter().position(|a| a == "--crop") {
+        let bound = |n: usize| -> u32 {
+            args[crop + n]
+                .parse()
+                .expect("--crop expects x, y, width and height")
+        };
+        camera.crop = Some(CropWindow::new(bound(1), bound(2), bound(3), bound(4)));
+    }
+
+    if args[1] == "--worker" {
+        distributed::serve(&mut camera, world, io::stdin().lock(), io::stdout().lock())
+            .expect("Worker failed");
+        return;
+    }
+
+    if let Some(aovs) = args.iter().position(|a| a == "--aovs") {
+        let target = args[aovs + 1].clone();
+        camera.aov_output = Some(if target.ends_with(".exr") {
+            AovOutput::Exr { path: target }
+        } else {
+            AovOutput::Files { prefix: target }
+        });
+    }
+
+    if let Some(frames) = args.iter().position(|a| a == "--frames") {
+        let first: u32 = args[frames + 1]
+            .parse()
+            .expect("--frames expects a first frame");
+        let last: u32 = args[frames + 2]
+            .parse()
+            .expect("--frames expects a last frame");
+        if last < first {
+            let _ = io::stderr()
+                .write(b"--frames expects the last frame not to come before the first\n");
+            process::exit(2);
+        }
+        // Spread the scene's motion, from time 0 to 1, across the frames
+        let duration = 1. / (last - first + 1) as f64;
+        let mut sequence = Sequence::new(first, last, duration, PathBuf::from(&args[1]));
+        sequence.start_time = -(first as f64) * duration;
+        sequence
+            .render(&mut camera, &world)
+            .expect("Could not write frames");
+        return;
+    }
+
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .write(true)
+        .open(args[1].clone())
+        .unwrap();
+
+    if let Some(workers) = args.iter().position(|a| a == "--workers") {
+        let workers = args[workers + 1]
+            .parse()
+            .expect("--workers expects a number of processes");
+        // Workers need the same scene, background and crop window as this process
+        let mut worker_args = vec!["--worker".to_string()];
+        for (flag, values) in [
+            ("--environment", 1),
+            ("--sky", 3),
+            ("--spectral", 0),
+            ("--crop", 4),
+            ("--mesh", 1),
+            ("--subdivide", 1),
+            ("--displace", 2),
+        ] {
+            if let Some(i) = args.iter().position(|a| a == flag) {
+                worker_args.extend_from_slice(&args[i..=i + values]);
+            }
+        }
+        let coordinator = Coordinator::new(args[0].clone(), worker_args, workers);
+        coordinator
+            .render(&mut camera, file)
+            .expect("Distributed render failed");
+        return;
+    }
+
     camera.render(file, world);
 }
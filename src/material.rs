@@ -1,11 +1,103 @@
-use crate::{colour::Colour, hittable::HitRecord, Ray};
+use std::{collections::HashMap, sync::Arc};
 
-pub trait Material {
+use crate::{
+    colour::Colour,
+    hittable::{HitRecord, SurfacePoint},
+    medium::Medium,
+    Ray, Vector,
+};
+
+/// A direction chosen by `Material::sample`.
+#[derive(Clone, Copy, Debug)]
+pub struct ScatterSample {
+    /// The direction light is scattered from, leaving the surface.
+    pub direction: Vector<f64, 3>,
+    /// The BSDF times the cosine of `direction` with the normal, divided by `pdf`.
+    pub weight: Colour,
+    /// The probability density, per unit solid angle, of choosing `direction`. For delta
+    /// lobes, the probability of choosing the lobe.
+    pub pdf: f64,
+    /// Whether `direction` came from a delta lobe, like a perfect mirror, which `eval` and
+    /// `pdf` can never see.
+    pub delta: bool,
+}
+
+/// How a surface scatters light. Directions are in world space: the direction towards the
+/// viewer is the reverse of `ray_in`'s, and the ray's wavelength and time are passed on too.
+pub trait Material: Send + Sync {
+    /// Chooses a direction to scatter `ray_in` towards, or returns `None` if it is absorbed.
+    fn sample(&self, ray_in: &Ray, record: &HitRecord) -> Option<ScatterSample>;
+
+    /// The BSDF for light arriving from unit `direction` and leaving along `ray_in` reversed,
+    /// times the cosine of `direction` with the normal. Delta lobes are left out.
+    fn eval(&self, _ray_in: &Ray, _record: &HitRecord, _direction: Vector<f64, 3>) -> Colour {
+        Colour::new([0., 0., 0.])
+    }
+
+    /// The probability density, per unit solid angle, of `sample` choosing unit `direction`.
+    /// Delta lobes are left out.
+    fn pdf(&self, _ray_in: &Ray, _record: &HitRecord, _direction: Vector<f64, 3>) -> f64 {
+        0.0
+    }
+
+    /// Scatters `ray_in`, returning false if it is absorbed.
     fn scatter(
         &self,
         ray_in: &Ray,
         record: &HitRecord,
         attenuation: &mut Colour,
         scattered: &mut Ray,
-    ) -> bool;
+    ) -> bool {
+        let Some(sample) = self.sample(ray_in, record) else {
+            return false;
+        };
+        *attenuation = sample.weight;
+        *scattered = Ray::new(record.p, sample.direction, Some(ray_in.time()))
+            .with_wavelength(ray_in.wavelength());
+        true
+    }
+
+    /// How much of the surface is really there at the hit, from 0 where it is cut away
+    /// entirely to 1 where it is solid.
+    fn opacity(&self, _surface: &SurfacePoint) -> f64 {
+        1.0
+    }
+
+    /// Light given off by the surface itself towards `ray_in`'s origin.
+    fn emitted(&self, _ray_in: &Ray, _record: &HitRecord) -> Colour {
+        Colour::new([0., 0., 0.])
+    }
+
+    /// The medium filling a closed surface made of this material, which rays refracted inside
+    /// travel through until they leave again.
+    fn medium(&self) -> Option<Medium> {
+        None
+    }
+}
+
+/// Numbers the materials of one scene as it is built, starting from 1, in the order they are
+/// first seen. A scene built in the same order therefore always gets the same IDs.
+#[derive(Default)]
+pub struct MaterialIds {
+    ids: HashMap<*const (), u32>,
+    // Keeps every numbered material alive, so that its address can't be reused by another
+    materials: Vec<Arc<dyn Material>>,
+}
+
+impl MaterialIds {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the ID of `material`, giving it the next one if it hasn't been seen before.
+    pub fn id(&mut self, material: &Arc<dyn Material>) -> u32 {
+        let address = Arc::as_ptr(material) as *const ();
+        if let Some(id) = self.ids.get(&address) {
+            return *id;
+        }
+        self.materials.push(material.clone());
+        let id = self.materials.len() as u32;
+        self.ids.insert(address, id);
+        id
+    }
 }
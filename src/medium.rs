@@ -0,0 +1,97 @@
+use std::f64::consts::PI;
+
+use rand::random;
+
+use crate::{colour::Colour, onb::Onb, Vector};
+
+/// A participating medium filling the inside of a closed surface. Light travels through it in
+/// straight lines between random scattering events.
+#[derive(Clone, Copy, Debug)]
+pub struct Medium {
+    /// The chance per unit distance, for each of red, green and blue, of light being scattered
+    /// or absorbed.
+    pub extinction: Colour,
+    /// The fraction of extinction events which scatter light rather than absorbing it.
+    pub albedo: Colour,
+    /// The Henyey-Greenstein asymmetry, from -1 (back scattering) through 0 (isotropic) to 1
+    /// (forward scattering).
+    pub anisotropy: f64,
+}
+
+/// What happens to light travelling through a medium towards a surface.
+#[derive(Clone, Copy, Debug)]
+pub enum MediumEvent {
+    /// The light scatters `distance` along its path, and is weighted by `weight`.
+    Scatter { distance: f64, weight: Colour },
+    /// The light reaches the surface, and is weighted by `weight`.
+    Pass { weight: Colour },
+}
+
+impl Medium {
+    pub fn new(extinction: Colour, albedo: Colour, anisotropy: f64) -> Self {
+        Self {
+            extinction,
+            albedo,
+            anisotropy,
+        }
+    }
+
+    /// Samples how far light travels before scattering, with a surface `max_distance` away.
+    /// Distances are sampled for one of red, green and blue at random, and weighted by the
+    /// average pdf of the three so that chromatic media stay unbiased.
+    pub fn sample_distance(&self, max_distance: f64) -> MediumEvent {
+        let extinction = [
+            self.extinction.x(),
+            self.extinction.y(),
+            self.extinction.z(),
+        ];
+        let channel = ((random::<f64>() * 3.) as usize).min(2);
+        let distance = -(1. - random::<f64>()).ln() / extinction[channel];
+
+        let transmittance =
+            |distance: f64| Colour::new(extinction.map(|sigma| (-sigma * distance).exp()));
+        let average = |c: Colour| (c.x() + c.y() + c.z()) / 3.;
+
+        if distance < max_distance {
+            let transmittance = transmittance(distance);
+            let pdf = average(transmittance.hadamard(self.extinction));
+            let weight = transmittance
+                .hadamard(self.extinction)
+                .hadamard(self.albedo)
+                / pdf;
+            MediumEvent::Scatter { distance, weight }
+        } else {
+            let transmittance = transmittance(max_distance);
+            let pdf = average(transmittance);
+            if pdf <= 0. {
+                return MediumEvent::Pass {
+                    weight: Colour::new([0., 0., 0.]),
+                };
+            }
+            MediumEvent::Pass {
+                weight: transmittance / pdf,
+            }
+        }
+    }
+
+    /// Samples the direction light travelling along unit `direction` scatters into. The phase
+    /// function is sampled exactly, so needs no weight.
+    pub fn sample_phase(&self, direction: Vector<f64, 3>) -> Vector<f64, 3> {
+        let g = self.anisotropy;
+        let u = random::<f64>();
+        let cos_theta = if g.abs() < 1e-3 {
+            1. - 2. * u
+        } else {
+            let s = (1. - g * g) / (1. + g - 2. * g * u);
+            ((1. + g * g - s * s) / (2. * g)).clamp(-1., 1.)
+        };
+        let sin_theta = (1. - cos_theta * cos_theta).max(0.).sqrt();
+        let phi = 2. * PI * random::<f64>();
+
+        Onb::new(direction).to_world(Vector::new([
+            sin_theta * phi.cos(),
+            sin_theta * phi.sin(),
+            cos_theta,
+        ]))
+    }
+}
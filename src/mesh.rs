@@ -0,0 +1,494 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::Path,
+    sync::Arc,
+};
+
+use linalg::Point;
+
+use crate::{
+    aabb::Aabb,
+    bvh::BvhNode,
+    colour::luminance,
+    hittable::{HitRecord, Hittable, SurfacePoint},
+    material::Material,
+    onb::Onb,
+    texture::Texture,
+    Interval, Ray, Vector,
+};
+
+/// The vertices and faces of a triangle mesh. Each vertex has a position and, optionally, a
+/// normal and texture coordinates.
+#[derive(Clone, Debug, Default)]
+pub struct MeshData {
+    pub positions: Vec<Point<f64, 3>>,
+    /// Either empty or one per vertex.
+    pub normals: Vec<Vector<f64, 3>>,
+    /// Either empty or one per vertex.
+    pub uvs: Vec<(f64, f64)>,
+    /// Indices of each triangle's vertices, anticlockwise when seen from the front.
+    pub triangles: Vec<[usize; 3]>,
+}
+
+impl MeshData {
+    pub fn load_obj(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::read_obj(BufReader::new(File::open(path)?))
+    }
+
+    /// Reads the positions, texture coordinates, normals and faces of a Wavefront OBJ file.
+    /// Faces with more than three vertices are split into fans of triangles; everything else,
+    /// including materials and groups, is ignored.
+    pub fn read_obj(input: impl BufRead) -> io::Result<Self> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        let mut positions = Vec::new();
+        let mut uvs = Vec::new();
+        let mut normals = Vec::new();
+        let mut mesh = MeshData::default();
+        // OBJ indexes positions, coordinates and normals separately, so each distinct
+        // combination becomes one of our vertices
+        let mut vertices: HashMap<(usize, Option<usize>, Option<usize>), usize> = HashMap::new();
+        let (mut has_uvs, mut has_normals) = (true, true);
+
+        for line in input.lines() {
+            let line = line?;
+            let mut fields = line.split_whitespace();
+            let Some(keyword) = fields.next() else {
+                continue;
+            };
+            let mut numbers = || -> io::Result<Vec<f64>> {
+                fields
+                    .by_ref()
+                    .map(|f| f.parse().map_err(|_| invalid("Invalid number in OBJ")))
+                    .collect()
+            };
+
+            match keyword {
+                "v" => {
+                    let n = numbers()?;
+                    if n.len() < 3 {
+                        return Err(invalid("OBJ vertex needs three coordinates"));
+                    }
+                    positions.push(Point::new([n[0], n[1], n[2]]));
+                }
+                "vt" => {
+                    let n = numbers()?;
+                    uvs.push((
+                        n.first().copied().unwrap_or(0.),
+                        n.get(1).copied().unwrap_or(0.),
+                    ));
+                }
+                "vn" => {
+                    let n = numbers()?;
+                    if n.len() < 3 {
+                        return Err(invalid("OBJ normal needs three components"));
+                    }
+                    normals.push(Vector::new([n[0], n[1], n[2]]).unit());
+                }
+                "f" => {
+                    let mut face = Vec::new();
+                    for corner in fields {
+                        // Indices start from 1, and negative ones count back from the end
+                        let mut parts = corner.split('/');
+                        let mut index = |count: usize| -> io::Result<Option<usize>> {
+                            match parts.next() {
+                                None | Some("") => Ok(None),
+                                Some(part) => {
+                                    let i: i64 =
+                                        part.parse().map_err(|_| invalid("Invalid OBJ index"))?;
+                                    let i = if i < 0 { count as i64 + i } else { i - 1 };
+                                    if i < 0 || i as usize >= count {
+                                        return Err(invalid("OBJ index out of range"));
+                                    }
+                                    Ok(Some(i as usize))
+                                }
+                            }
+                        };
+                        let key = (
+                            index(positions.len())?
+                                .ok_or_else(|| invalid("OBJ face missing a position"))?,
+                            index(uvs.len())?,
+                            index(normals.len())?,
+                        );
+                        has_uvs &= key.1.is_some();
+                        has_normals &= key.2.is_some();
+
+                        let vertex = *vertices.entry(key).or_insert_with(|| {
+                            mesh.positions.push(positions[key.0]);
+                            mesh.uvs.push(key.1.map_or((0., 0.), |i| uvs[i]));
+                            mesh.normals
+                                .push(key.2.map_or(Vector::default(), |i| normals[i]));
+                            mesh.positions.len() - 1
+                        });
+                        face.push(vertex);
+                    }
+                    if face.len() < 3 {
+                        return Err(invalid("OBJ face needs at least three vertices"));
+                    }
+                    for i in 1..face.len() - 1 {
+                        mesh.triangles.push([face[0], face[i], face[i + 1]]);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if !has_uvs {
+            mesh.uvs.clear();
+        }
+        if !has_normals {
+            mesh.normals.clear();
+        }
+        Ok(mesh)
+    }
+
+    /// Sets each vertex normal to the area-weighted average of its triangles' normals.
+    pub fn compute_normals(&mut self) {
+        let mut normals = vec![Vector::new([0., 0., 0.]); self.positions.len()];
+        for &[a, b, c] in &self.triangles {
+            let p = &self.positions;
+            // The cross product's length is twice the area, which gives the weighting
+            let normal = (p[b] - p[a]).cross(p[c] - p[a]);
+            for i in [a, b, c] {
+                normals[i] = normals[i] + normal;
+            }
+        }
+        self.normals = normals
+            .into_iter()
+            .map(|n| if n.near_zero() { n } else { n.unit() })
+            .collect();
+    }
+
+    /// Splits every triangle into four, at the midpoints of its edges.
+    pub fn subdivide(&mut self) {
+        let mut midpoints: HashMap<(usize, usize), usize> = HashMap::new();
+        let mut triangles = Vec::with_capacity(self.triangles.len() * 4);
+
+        for [a, b, c] in std::mem::take(&mut self.triangles) {
+            let mut midpoint = |i: usize, j: usize| {
+                *midpoints.entry((i.min(j), i.max(j))).or_insert_with(|| {
+                    self.positions
+                        .push((self.positions[i] + self.positions[j]) / 2.);
+                    if !self.normals.is_empty() {
+                        let normal = self.normals[i] + self.normals[j];
+                        self.normals.push(if normal.near_zero() {
+                            normal
+                        } else {
+                            normal.unit()
+                        });
+                    }
+                    if !self.uvs.is_empty() {
+                        let (ui, vi) = self.uvs[i];
+                        let (uj, vj) = self.uvs[j];
+                        self.uvs.push(((ui + uj) / 2., (vi + vj) / 2.));
+                    }
+                    self.positions.len() - 1
+                })
+            };
+            let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+            triangles.extend([[a, ab, ca], [ab, b, bc], [ca, bc, c], [ab, bc, ca]]);
+        }
+
+        self.triangles = triangles;
+    }
+
+    /// Moves each vertex along its normal by the luminance of `height` times `scale`, then
+    /// recomputes the normals. Meshes usually need subdividing first to have enough vertices to
+    /// show the detail. Vertices split along texture seams may pull apart.
+    pub fn displace(&mut self, height: &dyn Texture, scale: f64) {
+        if self.normals.is_empty() {
+            self.compute_normals();
+        }
+        for i in 0..self.positions.len() {
+            let (u, v) = self.uvs.get(i).copied().unwrap_or((0., 0.));
+            let p = self.positions[i];
+            let offset = luminance(height.value(u, v, p)) * scale;
+            self.positions[i] = p + self.normals[i] * offset;
+        }
+        self.compute_normals();
+    }
+}
+
+/// A triangle mesh, hit as a single object. A mesh without any faces is never hit.
+pub struct Mesh {
+    bvh: Option<BvhNode>,
+}
+
+impl Mesh {
+    /// A mesh of `material`, whose hits report `material_id` to the material ID AOV, e.g. from
+    /// a scene's `MaterialIds`.
+    pub fn new(data: MeshData, material: Arc<dyn Material>, material_id: u32) -> Self {
+        let data = Arc::new(data);
+        let triangles = (0..data.triangles.len())
+            .map(|index| {
+                Arc::new(Triangle {
+                    mesh: data.clone(),
+                    index,
+                    material: material.clone(),
+                    material_id,
+                }) as Arc<dyn Hittable>
+            })
+            .collect();
+        Self {
+            bvh: (!data.triangles.is_empty()).then(|| BvhNode::new(triangles)),
+        }
+    }
+}
+
+impl Hittable for Mesh {
+    fn hit(&self, ray: &Ray, ray_t: Interval, record: &mut HitRecord) -> bool {
+        match &self.bvh {
+            Some(bvh) => bvh.hit(ray, ray_t, record),
+            None => false,
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        match &self.bvh {
+            Some(bvh) => bvh.bounding_box(),
+            None => Aabb::empty(),
+        }
+    }
+}
+
+/// One triangle of a mesh.
+struct Triangle {
+    mesh: Arc<MeshData>,
+    index: usize,
+    material: Arc<dyn Material>,
+    material_id: u32,
+}
+
+impl Triangle {
+    fn vertices(&self) -> [usize; 3] {
+        self.mesh.triangles[self.index]
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, ray: &Ray, ray_t: Interval, record: &mut HitRecord) -> bool {
+        let [a, b, c] = self.vertices();
+        let positions = &self.mesh.positions;
+        let (p0, p1, p2) = (positions[a], positions[b], positions[c]);
+
+        // Möller-Trumbore, solving for the distance and barycentric coordinates together
+        let e1 = p1 - p0;
+        let e2 = p2 - p0;
+        let pvec = ray.direction().cross(e2);
+        let det = e1.dot(&pvec);
+        if det.abs() < 1e-12 {
+            return false;
+        }
+        let inverse = 1. / det;
+
+        let tvec = ray.origin() - p0;
+        let b1 = tvec.dot(&pvec) * inverse;
+        if !(0. ..=1.).contains(&b1) {
+            return false;
+        }
+        let qvec = tvec.cross(e1);
+        let b2 = ray.direction().dot(&qvec) * inverse;
+        if b2 < 0. || b1 + b2 > 1. {
+            return false;
+        }
+        let distance = e2.dot(&qvec) * inverse;
+        if !ray_t.surrounds(distance) {
+            return false;
+        }
+        let b0 = 1. - b1 - b2;
+
+        let uvs = if self.mesh.uvs.is_empty() {
+            [(0., 0.), (1., 0.), (0., 1.)]
+        } else {
+            [self.mesh.uvs[a], self.mesh.uvs[b], self.mesh.uvs[c]]
+        };
+        let p = ray.at(distance);
+        let outward_normal = e1.cross(e2).unit();
+        let surface = SurfacePoint {
+            p,
+            u: b0 * uvs[0].0 + b1 * uvs[1].0 + b2 * uvs[2].0,
+            v: b0 * uvs[0].1 + b1 * uvs[1].1 + b2 * uvs[2].1,
+            time: ray.time(),
+            front_face: ray.direction().dot(&outward_normal) < 0.,
+        };
+
+        // Alpha masks may cut the surface away here
+        if !surface.is_opaque(self.material.as_ref()) {
+            return false;
+        }
+
+        record.distance = distance;
+        record.p = p;
+        record.set_face_normal(ray, &outward_normal);
+        (record.u, record.v) = (surface.u, surface.v);
+        record.time = surface.time;
+        record.material = self.material.clone();
+        record.material_id = self.material_id;
+
+        // Solve for the tangents from how position and coordinates change along two edges
+        let (du02, dv02) = (uvs[0].0 - uvs[2].0, uvs[0].1 - uvs[2].1);
+        let (du12, dv12) = (uvs[1].0 - uvs[2].0, uvs[1].1 - uvs[2].1);
+        let (dp02, dp12) = (p0 - p2, p1 - p2);
+        let uv_det = du02 * dv12 - dv02 * du12;
+        if uv_det.abs() < 1e-12 {
+            let frame = Onb::new(record.normal);
+            (record.dpdu, record.dpdv) = (frame.u, frame.v);
+        } else {
+            record.dpdu = (dp02 * dv12 - dp12 * dv02) / uv_det;
+            record.dpdv = (dp12 * du02 - dp02 * du12) / uv_det;
+        }
+
+        if !self.mesh.normals.is_empty() {
+            // Smooth shading, from the vertex normals
+            let normals = &self.mesh.normals;
+            let normal = normals[a] * b0 + normals[b] * b1 + normals[c] * b2;
+            if !normal.near_zero() {
+                let normal = normal.unit();
+                record.normal = if normal.dot(&record.normal) < 0. {
+                    -normal
+                } else {
+                    normal
+                };
+            }
+        }
+
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let [a, b, c] = self.vertices();
+        let positions = &self.mesh.positions;
+        Aabb::union(
+            Aabb::from_points(positions[a], positions[b]),
+            Aabb::from_points(positions[c], positions[c]),
+        )
+        .padded()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lambertian::Lambertian, texture::SolidColour};
+
+    fn read(obj: &str) -> io::Result<MeshData> {
+        MeshData::read_obj(obj.as_bytes())
+    }
+
+    const SQUARE: &str = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n";
+
+    #[test]
+    fn splits_polygons_into_fans() {
+        let mesh = read(&format!("{SQUARE}v 0.5 2 0\nf 1 2 3 4\nf 1 3 5 4 2\n")).unwrap();
+        assert_eq!(mesh.positions.len(), 5);
+        assert_eq!(
+            mesh.triangles,
+            vec![[0, 1, 2], [0, 2, 3], [0, 2, 4], [0, 4, 3], [0, 3, 1]]
+        );
+        assert!(mesh.uvs.is_empty());
+        assert!(mesh.normals.is_empty());
+    }
+
+    #[test]
+    fn negative_indices_count_back_from_the_end() {
+        let absolute = read(&format!("{SQUARE}f 2 3 4\n")).unwrap();
+        let relative = read(&format!("{SQUARE}f -3 -2 -1\n")).unwrap();
+        assert_eq!(absolute.positions, relative.positions);
+        assert_eq!(absolute.triangles, relative.triangles);
+    }
+
+    #[test]
+    fn reads_coordinates_and_normals() {
+        let obj = format!("{SQUARE}vt 0 0\nvt 1 0\nvt 1 1\nvn 0 0 2\nf 1/1/1 2/2/1 3/3/1\n");
+        let mesh = read(&obj).unwrap();
+        assert_eq!(mesh.uvs, vec![(0., 0.), (1., 0.), (1., 1.)]);
+        assert_eq!(mesh.normals, vec![Vector::new([0., 0., 1.]); 3]);
+
+        // Without texture coordinates, only the normals are kept
+        let mesh = read(&format!("{SQUARE}vn 0 0 1\nf 1//1 2//1 3//1\n")).unwrap();
+        assert!(mesh.uvs.is_empty());
+        assert_eq!(mesh.normals.len(), 3);
+    }
+
+    #[test]
+    fn shares_vertices_with_the_same_indices() {
+        let obj = format!("{SQUARE}vt 0 0\nvt 1 1\nf 1/1 2/1 3/1\nf 1/1 3/1 4/1\nf 1/2 3/2 4/2\n");
+        let mesh = read(&obj).unwrap();
+        // The last face uses different coordinates, so gets vertices of its own
+        assert_eq!(mesh.positions.len(), 7);
+        assert_eq!(mesh.triangles[1], [0, 2, 3]);
+        assert_eq!(mesh.triangles[2], [4, 5, 6]);
+    }
+
+    #[test]
+    fn rejects_bad_faces() {
+        for face in [
+            "f 1 2 5",
+            "f 0 1 2",
+            "f -5 1 2",
+            "f 1 2",
+            "f 1/3 2/3 3/3",
+            "f a b c",
+        ] {
+            let error = read(&format!("{SQUARE}{face}\n")).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{face}");
+        }
+        assert!(read("v 1 2\n").is_err());
+    }
+
+    #[test]
+    fn subdivides_into_four_sharing_midpoints() {
+        let mut mesh = read(&format!("{SQUARE}f 1 2 3\n")).unwrap();
+        mesh.subdivide();
+        assert_eq!(mesh.triangles.len(), 4);
+        assert_eq!(mesh.positions.len(), 6);
+
+        // Two triangles share the midpoint of their common edge
+        let mut mesh = read(&format!("{SQUARE}f 1 2 3 4\n")).unwrap();
+        mesh.subdivide();
+        assert_eq!(mesh.triangles.len(), 8);
+        assert_eq!(mesh.positions.len(), 9);
+        mesh.subdivide();
+        assert_eq!(mesh.triangles.len(), 32);
+        assert_eq!(mesh.positions.len(), 25);
+    }
+
+    #[test]
+    fn displaces_along_normals() {
+        let mut mesh = read(&format!("{SQUARE}f 1 2 3 4\n")).unwrap();
+        mesh.displace(&SolidColour::constant(0.5), 2.);
+        for p in &mesh.positions {
+            assert!((p.z() - 1.).abs() < 1e-12);
+        }
+        assert_eq!(mesh.normals, vec![Vector::new([0., 0., 1.]); 4]);
+    }
+
+    fn mesh(obj: &str) -> Mesh {
+        Mesh::new(read(obj).unwrap(), Arc::new(Lambertian::default()), 1)
+    }
+
+    fn hits(mesh: &Mesh) -> bool {
+        let ray = Ray::new(
+            Point::new([0.5, 0.25, 1.]),
+            Vector::new([0., 0., -1.]),
+            None,
+        );
+        mesh.hit(
+            &ray,
+            Interval::new(0.001, f64::INFINITY),
+            &mut HitRecord::default(),
+        )
+    }
+
+    #[test]
+    fn meshes_without_faces_are_never_hit() {
+        for obj in ["", SQUARE] {
+            let mesh = mesh(obj);
+            assert!(!hits(&mesh));
+            assert!(mesh.bounding_box().is_empty());
+        }
+        assert!(hits(&mesh(&format!("{SQUARE}f 1 2 3\n"))));
+    }
+}
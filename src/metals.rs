@@ -1,34 +1,230 @@
 use linalg::vector::Vector;
 
-use crate::{colour::Colour, hittable::HitRecord, material::Material, Ray};
+use crate::{
+    colour::Colour,
+    hittable::HitRecord,
+    material::{Material, ScatterSample},
+    microfacet::Ggx,
+    onb::Onb,
+    Ray,
+};
 
+/// How much light a metal reflects at each angle.
+#[derive(Clone, Copy, Debug)]
+pub enum Fresnel {
+    /// Schlick's approximation, from the colour reflected head-on.
+    Schlick(Colour),
+    /// The exact conductor equations, from the complex refractive index `eta + ik` in each of red,
+    /// green and blue.
+    Conductor { eta: Colour, k: Colour },
+}
+
+impl Default for Fresnel {
+    fn default() -> Self {
+        Fresnel::Schlick(Colour::new([1., 1., 1.]))
+    }
+}
+
+impl Fresnel {
+    fn reflectance(&self, cos_theta: f64) -> Colour {
+        match *self {
+            Fresnel::Schlick(f0) => {
+                let white = Colour::new([1., 1., 1.]);
+                f0 + (white - f0) * (1. - cos_theta).powi(5)
+            }
+            Fresnel::Conductor { eta, k } => Colour::new([
+                conductor_reflectance(cos_theta, eta.x(), k.x()),
+                conductor_reflectance(cos_theta, eta.y(), k.y()),
+                conductor_reflectance(cos_theta, eta.z(), k.z()),
+            ]),
+        }
+    }
+}
+
+/// The unpolarised reflectance of a conductor with complex refractive index `eta + ik`.
+fn conductor_reflectance(cos_theta: f64, eta: f64, k: f64) -> f64 {
+    let cos2 = cos_theta * cos_theta;
+    let sin2 = 1. - cos2;
+
+    let t0 = eta * eta - k * k - sin2;
+    let a2_plus_b2 = (t0 * t0 + 4. * eta * eta * k * k).sqrt();
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.).sqrt();
+
+    let t1 = a2_plus_b2 + cos2;
+    let t2 = 2. * cos_theta * a;
+    let rs = (t1 - t2) / (t1 + t2);
+
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let rp = rs * (t3 - t4) / (t3 + t4);
+
+    (rs + rp) / 2.
+}
+
+/// A metal, modelled as a surface of tiny mirrors (microfacets) whose orientations follow the
+/// GGX distribution. Anisotropic highlights are stretched along the hit's tangent, which
+/// follows increasing `u`.
 #[derive(Clone, Copy, Default, Debug)]
 pub struct Metal {
-    albedo: Colour,
-    fuzz: f64,
+    fresnel: Fresnel,
+    distribution: Ggx,
 }
 
 impl Metal {
+    /// A metal reflecting `albedo` head-on, with `fuzz` used as its roughness.
     pub fn new(albedo: Colour, fuzz: f64) -> Self {
-        let fuzz = fuzz.clamp(0.0, 1.0);
-        Self { albedo, fuzz }
+        Self::rough(albedo, fuzz, 0.)
+    }
+
+    /// A metal reflecting `albedo` head-on. `roughness` runs from 0 (a mirror) to 1, and
+    /// `anisotropy` from 0 to 1 stretches highlights along the surface's tangent.
+    pub fn rough(albedo: Colour, roughness: f64, anisotropy: f64) -> Self {
+        Self::with_fresnel(Fresnel::Schlick(albedo), roughness, anisotropy)
+    }
+
+    /// A metal with complex refractive index `eta + ik`, given for red, green and blue.
+    pub fn conductor(eta: Colour, k: Colour, roughness: f64, anisotropy: f64) -> Self {
+        Self::with_fresnel(Fresnel::Conductor { eta, k }, roughness, anisotropy)
+    }
+
+    pub fn with_fresnel(fresnel: Fresnel, roughness: f64, anisotropy: f64) -> Self {
+        Self {
+            fresnel,
+            distribution: Ggx::new(roughness, anisotropy),
+        }
+    }
+
+    pub fn gold(roughness: f64) -> Self {
+        Self::conductor(
+            Colour::new([0.143, 0.374, 1.442]),
+            Colour::new([3.983, 2.385, 1.603]),
+            roughness,
+            0.,
+        )
+    }
+
+    pub fn silver(roughness: f64) -> Self {
+        Self::conductor(
+            Colour::new([0.155, 0.117, 0.138]),
+            Colour::new([4.828, 3.122, 2.147]),
+            roughness,
+            0.,
+        )
+    }
+
+    pub fn copper(roughness: f64) -> Self {
+        Self::conductor(
+            Colour::new([0.200, 0.924, 1.102]),
+            Colour::new([3.912, 2.452, 2.142]),
+            roughness,
+            0.,
+        )
+    }
+
+    pub fn aluminium(roughness: f64) -> Self {
+        Self::conductor(
+            Colour::new([1.657, 0.880, 0.521]),
+            Colour::new([9.224, 6.270, 4.837]),
+            roughness,
+            0.,
+        )
+    }
+
+    pub fn iron(roughness: f64) -> Self {
+        Self::conductor(
+            Colour::new([2.912, 2.950, 2.585]),
+            Colour::new([3.089, 2.932, 2.767]),
+            roughness,
+            0.,
+        )
     }
 }
 
 impl Material for Metal {
-    fn scatter(
-        &self,
-        ray_in: &Ray,
-        record: &HitRecord,
-        attenuation: &mut Colour,
-        scattered: &mut Ray,
-    ) -> bool {
-        let mut reflected = Vector::reflect(ray_in.direction().unit(), record.normal);
-        reflected = reflected.unit() + (self.fuzz * Vector::random_unit_vector());
-
-        *scattered = Ray::new(record.p, reflected, Some(ray_in.time()));
-        *attenuation = self.albedo;
-
-        scattered.direction().dot(&record.normal) > 0.0
+    fn sample(&self, ray_in: &Ray, record: &HitRecord) -> Option<ScatterSample> {
+        let onb = Onb::from_tangent(record.normal, record.tangent());
+        let wo = onb.to_local(-ray_in.direction().unit());
+        if wo.z() <= 0. {
+            return None;
+        }
+
+        if self.distribution.is_smooth() {
+            return Some(ScatterSample {
+                direction: Vector::reflect(ray_in.direction().unit(), record.normal),
+                weight: self.fresnel.reflectance(wo.z()),
+                pdf: 1.,
+                delta: true,
+            });
+        }
+
+        let h = self.distribution.sample_visible_normal(wo);
+        let wi = Vector::reflect(-wo, h);
+        if wi.z() <= 0. {
+            return None;
+        }
+
+        // With visible normal sampling, the BRDF times cosine over the pdf reduces to the
+        // Fresnel term times the masking of the light given the masking of the view
+        let masking = self.distribution.g2(wo, wi) / self.distribution.g1(wo);
+        Some(ScatterSample {
+            direction: onb.to_world(wi),
+            weight: self.fresnel.reflectance(wo.dot(&h)) * masking,
+            pdf: self.distribution.visible_normal_pdf(wo, h) / (4. * wo.dot(&h)),
+            delta: false,
+        })
+    }
+
+    fn eval(&self, ray_in: &Ray, record: &HitRecord, direction: Vector<f64, 3>) -> Colour {
+        let onb = Onb::from_tangent(record.normal, record.tangent());
+        let wo = onb.to_local(-ray_in.direction().unit());
+        let wi = onb.to_local(direction);
+        if self.distribution.is_smooth() || wo.z() <= 0. || wi.z() <= 0. {
+            return Colour::new([0., 0., 0.]);
+        }
+
+        let h = (wo + wi).unit();
+        let d = self.distribution.d(h);
+        let g = self.distribution.g2(wo, wi);
+        // The cosine of the incoming light cancels with the BRDF's denominator
+        self.fresnel.reflectance(wo.dot(&h)) * (d * g / (4. * wo.z()))
+    }
+
+    fn pdf(&self, ray_in: &Ray, record: &HitRecord, direction: Vector<f64, 3>) -> f64 {
+        let onb = Onb::from_tangent(record.normal, record.tangent());
+        let wo = onb.to_local(-ray_in.direction().unit());
+        let wi = onb.to_local(direction);
+        if self.distribution.is_smooth() || wo.z() <= 0. || wi.z() <= 0. {
+            return 0.;
+        }
+
+        let h = (wo + wi).unit();
+        self.distribution.visible_normal_pdf(wo, h) / (4. * wo.dot(&h))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conductors_head_on_match_the_normal_incidence_formula() {
+        for (eta, k) in [(0.143, 3.983), (1.442, 1.603), (2.9, 3.0), (1.5, 0.)] {
+            let expected = ((eta - 1.) * (eta - 1.) + k * k) / ((eta + 1.) * (eta + 1.) + k * k);
+            let reflectance = conductor_reflectance(1., eta, k);
+            assert!(
+                (reflectance - expected).abs() < 1e-12,
+                "{eta} + {k}i: {reflectance}, not {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn conductors_reflect_everything_at_grazing_angles() {
+        let fresnel = Fresnel::Conductor {
+            eta: Colour::new([0.2, 0.9, 1.1]),
+            k: Colour::new([3.9, 2.5, 2.1]),
+        };
+        let reflectance = fresnel.reflectance(1e-9);
+        assert!((reflectance - Colour::new([1., 1., 1.])).length() < 1e-6);
     }
 }
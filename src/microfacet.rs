@@ -0,0 +1,145 @@
+use std::f64::consts::PI;
+
+use rand::random;
+
+use crate::Vector;
+
+/// The GGX (Trowbridge-Reitz) distribution of microfacet normals, in coordinates local to a
+/// surface whose normal is +z.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Ggx {
+    /// Widths along the surface's two tangents.
+    pub alpha_x: f64,
+    pub alpha_y: f64,
+}
+
+impl Ggx {
+    /// Below this width the surface is treated as perfectly smooth.
+    const SMOOTH: f64 = 1e-3;
+
+    /// `roughness` runs from 0 (perfectly smooth) to 1, and `anisotropy` from 0 to 1 stretches
+    /// highlights along the first tangent.
+    pub fn new(roughness: f64, anisotropy: f64) -> Self {
+        // Squaring the roughness makes it perceptually linear
+        let alpha = roughness.clamp(0., 1.).powi(2);
+        let aspect = (1. - 0.9 * anisotropy.clamp(0., 1.)).sqrt();
+        Self {
+            alpha_x: alpha / aspect,
+            alpha_y: alpha * aspect,
+        }
+    }
+
+    pub fn is_smooth(&self) -> bool {
+        self.alpha_x.max(self.alpha_y) < Self::SMOOTH
+    }
+
+    /// The density of microfacets facing `h`, per unit projected area.
+    pub fn d(&self, h: Vector<f64, 3>) -> f64 {
+        let x = h.x() / self.alpha_x;
+        let y = h.y() / self.alpha_y;
+        let denominator = x * x + y * y + h.z() * h.z();
+        1. / (PI * self.alpha_x * self.alpha_y * denominator * denominator)
+    }
+
+    /// Smith's auxiliary function for a local direction `w`, from which masking follows.
+    pub fn lambda(&self, w: Vector<f64, 3>) -> f64 {
+        let tan2 = (self.alpha_x * self.alpha_x * w.x() * w.x()
+            + self.alpha_y * self.alpha_y * w.y() * w.y())
+            / (w.z() * w.z());
+        ((1. + tan2).sqrt() - 1.) / 2.
+    }
+
+    /// The fraction of microfacets visible from `w`.
+    pub fn g1(&self, w: Vector<f64, 3>) -> f64 {
+        1. / (1. + self.lambda(w))
+    }
+
+    /// The fraction of microfacets visible from both `wo` and `wi`.
+    pub fn g2(&self, wo: Vector<f64, 3>, wi: Vector<f64, 3>) -> f64 {
+        1. / (1. + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Samples a microfacet normal from those visible from `wo`, after Heitz, "Sampling the GGX
+    /// Distribution of Visible Normals" (2018).
+    pub fn sample_visible_normal(&self, wo: Vector<f64, 3>) -> Vector<f64, 3> {
+        // Stretch the view so the distribution becomes a hemisphere
+        let vh = Vector::new([self.alpha_x * wo.x(), self.alpha_y * wo.y(), wo.z()]).unit();
+
+        let length2 = vh.x() * vh.x() + vh.y() * vh.y();
+        let t1 = if length2 > 0. {
+            Vector::new([-vh.y(), vh.x(), 0.]) / length2.sqrt()
+        } else {
+            Vector::new([1., 0., 0.])
+        };
+        let t2 = vh.cross(t1);
+
+        // Sample the projected area of the hemisphere, which is partly hidden behind itself
+        let r = random::<f64>().sqrt();
+        let phi = 2. * PI * random::<f64>();
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1. + vh.z());
+        let p2 = (1. - s) * (1. - p1 * p1).sqrt() + s * r * phi.sin();
+        let nh = t1 * p1 + t2 * p2 + vh * (1. - p1 * p1 - p2 * p2).max(0.).sqrt();
+
+        // Unstretch back to the microfacet normal
+        Vector::new([self.alpha_x * nh.x(), self.alpha_y * nh.y(), nh.z().max(0.)]).unit()
+    }
+
+    /// The density, per unit solid angle, of `sample_visible_normal` returning `h` given `wo`.
+    pub fn visible_normal_pdf(&self, wo: Vector<f64, 3>, h: Vector<f64, 3>) -> f64 {
+        self.g1(wo) * wo.dot(&h).max(0.) * self.d(h) / wo.z().abs()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn visible_normal_pdf_matches_the_samples() {
+        const BINS: usize = 8;
+        const SAMPLES: usize = 200_000;
+        let distribution = Ggx::new(0.6, 0.5);
+        let wo = Vector::new([0.5, 0.3, 0.6]).unit();
+
+        // Count samples by cos(theta) and phi, both in equal steps, so every bin covers the
+        // same solid angle
+        let bin = |h: Vector<f64, 3>| {
+            let z = ((h.z() * BINS as f64) as usize).min(BINS - 1);
+            let phi = h.y().atan2(h.x()).rem_euclid(2. * PI);
+            let phi = ((phi / (2. * PI) * BINS as f64) as usize).min(BINS - 1);
+            z * BINS + phi
+        };
+        let mut counts = [0usize; BINS * BINS];
+        for _ in 0..SAMPLES {
+            counts[bin(distribution.sample_visible_normal(wo))] += 1;
+        }
+
+        // Integrate the pdf over each bin with the midpoint rule
+        const STEPS: usize = 16;
+        let (dz, dphi) = (1. / BINS as f64, 2. * PI / BINS as f64);
+        let mut total = 0.;
+        for (index, count) in counts.iter().enumerate() {
+            let (z0, phi0) = ((index / BINS) as f64 * dz, (index % BINS) as f64 * dphi);
+            let mut expected = 0.;
+            for a in 0..STEPS {
+                for b in 0..STEPS {
+                    let z = z0 + (a as f64 + 0.5) * dz / STEPS as f64;
+                    let phi = phi0 + (b as f64 + 0.5) * dphi / STEPS as f64;
+                    let r = (1. - z * z).sqrt();
+                    let h = Vector::new([r * phi.cos(), r * phi.sin(), z]);
+                    expected += distribution.visible_normal_pdf(wo, h);
+                }
+            }
+            expected *= dz * dphi / (STEPS * STEPS) as f64;
+            total += expected;
+
+            let observed = *count as f64 / SAMPLES as f64;
+            assert!(
+                (observed - expected).abs() < 0.003,
+                "bin {index}: sampled {observed}, pdf gives {expected}"
+            );
+        }
+        assert!((total - 1.).abs() < 0.01, "{total}");
+    }
+}
@@ -0,0 +1,235 @@
+use std::sync::Arc;
+
+use rand::random;
+
+use crate::{
+    colour::{luminance, Colour},
+    hittable::{HitRecord, SurfacePoint},
+    material::{Material, ScatterSample},
+    medium::Medium,
+    metals::Metal,
+    texture::Texture,
+    Ray, Vector,
+};
+
+/// Blends two materials, picking one at random at each hit. Where `weight` is 0 the surface
+/// is entirely `a`, and where it is 1 entirely `b`.
+pub struct MixMaterial {
+    pub a: Arc<dyn Material>,
+    pub b: Arc<dyn Material>,
+    /// Read as the luminance of the texture at each hit.
+    pub weight: Arc<dyn Texture>,
+}
+
+impl MixMaterial {
+    pub fn new(a: Arc<dyn Material>, b: Arc<dyn Material>, weight: Arc<dyn Texture>) -> Self {
+        Self { a, b, weight }
+    }
+
+    fn weight(&self, surface: &SurfacePoint) -> f64 {
+        luminance(self.weight.value(surface.u, surface.v, surface.p)).clamp(0., 1.)
+    }
+}
+
+impl Material for MixMaterial {
+    fn sample(&self, ray_in: &Ray, record: &HitRecord) -> Option<ScatterSample> {
+        let weight = self.weight(&record.surface());
+        sample_layers(
+            self,
+            [&*self.a, &*self.b],
+            [1. - weight, weight],
+            ray_in,
+            record,
+        )
+    }
+
+    fn eval(&self, ray_in: &Ray, record: &HitRecord, direction: Vector<f64, 3>) -> Colour {
+        let weight = self.weight(&record.surface());
+        self.a.eval(ray_in, record, direction) * (1. - weight)
+            + self.b.eval(ray_in, record, direction) * weight
+    }
+
+    fn pdf(&self, ray_in: &Ray, record: &HitRecord, direction: Vector<f64, 3>) -> f64 {
+        let weight = self.weight(&record.surface());
+        self.a.pdf(ray_in, record, direction) * (1. - weight)
+            + self.b.pdf(ray_in, record, direction) * weight
+    }
+
+    fn opacity(&self, surface: &SurfacePoint) -> f64 {
+        let weight = self.weight(surface);
+        self.a.opacity(surface) * (1. - weight) + self.b.opacity(surface) * weight
+    }
+
+    fn emitted(&self, ray_in: &Ray, record: &HitRecord) -> Colour {
+        let weight = self.weight(&record.surface());
+        self.a.emitted(ray_in, record) * (1. - weight) + self.b.emitted(ray_in, record) * weight
+    }
+
+    /// The interior can't vary over the surface, so is whichever of the two materials has one.
+    fn medium(&self) -> Option<Medium> {
+        self.a.medium().or_else(|| self.b.medium())
+    }
+}
+
+/// A clear, glossy coat, like varnish or lacquer, over another material. Light which isn't
+/// reflected by the coat passes through it, tinted, to the material below and back out again.
+pub struct Coated {
+    pub base: Arc<dyn Material>,
+    /// From 0 (polished) to 1.
+    pub roughness: f64,
+    /// Refractive index of the coat, which sets how strongly it reflects.
+    pub ior: f64,
+    /// Colour of the coat, applied to light on each pass through it.
+    pub tint: Colour,
+}
+
+impl Coated {
+    pub fn new(base: Arc<dyn Material>, roughness: f64) -> Self {
+        Self {
+            base,
+            roughness,
+            ior: 1.5,
+            tint: Colour::new([1., 1., 1.]),
+        }
+    }
+
+    fn coat(&self) -> Metal {
+        Metal::rough(Colour::new([1., 1., 1.]), self.roughness, 0.)
+    }
+
+    /// The probability of light seen along `ray_in` being reflected by the coat.
+    fn reflectance(&self, ray_in: &Ray, record: &HitRecord) -> f64 {
+        let cos_theta = (-ray_in.direction().unit())
+            .dot(&record.normal)
+            .clamp(0., 1.);
+        let r0 = ((1. - self.ior) / (1. + self.ior)).powi(2);
+        r0 + (1. - r0) * (1. - cos_theta).powi(5)
+    }
+
+    fn base_tint(&self) -> Colour {
+        self.tint.hadamard(self.tint)
+    }
+}
+
+impl Material for Coated {
+    fn sample(&self, ray_in: &Ray, record: &HitRecord) -> Option<ScatterSample> {
+        let reflectance = self.reflectance(ray_in, record);
+        let coat = self.coat();
+        let base = Tinted {
+            material: &*self.base,
+            tint: self.base_tint(),
+        };
+        sample_layers(
+            self,
+            [&coat, &base],
+            [reflectance, 1. - reflectance],
+            ray_in,
+            record,
+        )
+    }
+
+    fn eval(&self, ray_in: &Ray, record: &HitRecord, direction: Vector<f64, 3>) -> Colour {
+        let reflectance = self.reflectance(ray_in, record);
+        self.coat().eval(ray_in, record, direction) * reflectance
+            + self
+                .base
+                .eval(ray_in, record, direction)
+                .hadamard(self.base_tint())
+                * (1. - reflectance)
+    }
+
+    fn pdf(&self, ray_in: &Ray, record: &HitRecord, direction: Vector<f64, 3>) -> f64 {
+        let reflectance = self.reflectance(ray_in, record);
+        self.coat().pdf(ray_in, record, direction) * reflectance
+            + self.base.pdf(ray_in, record, direction) * (1. - reflectance)
+    }
+
+    fn opacity(&self, surface: &SurfacePoint) -> f64 {
+        self.base.opacity(surface)
+    }
+
+    fn emitted(&self, ray_in: &Ray, record: &HitRecord) -> Colour {
+        self.base.emitted(ray_in, record).hadamard(self.tint)
+            * (1. - self.reflectance(ray_in, record))
+    }
+
+    fn medium(&self) -> Option<Medium> {
+        self.base.medium()
+    }
+}
+
+/// A material with its scattered light multiplied by `tint`.
+pub(crate) struct Tinted<'a> {
+    pub material: &'a dyn Material,
+    pub tint: Colour,
+}
+
+impl Material for Tinted<'_> {
+    fn sample(&self, ray_in: &Ray, record: &HitRecord) -> Option<ScatterSample> {
+        let mut sample = self.material.sample(ray_in, record)?;
+        sample.weight = sample.weight.hadamard(self.tint);
+        Some(sample)
+    }
+
+    fn eval(&self, ray_in: &Ray, record: &HitRecord, direction: Vector<f64, 3>) -> Colour {
+        self.material
+            .eval(ray_in, record, direction)
+            .hadamard(self.tint)
+    }
+
+    fn pdf(&self, ray_in: &Ray, record: &HitRecord, direction: Vector<f64, 3>) -> f64 {
+        self.material.pdf(ray_in, record, direction)
+    }
+}
+
+/// Samples `mixture`, made of `layers` each chosen with the given probability. Directions from
+/// non-delta lobes are weighted by the whole mixture, which any of the layers could have
+/// produced.
+pub(crate) fn sample_layers<const N: usize>(
+    mixture: &dyn Material,
+    layers: [&dyn Material; N],
+    probabilities: [f64; N],
+    ray_in: &Ray,
+    record: &HitRecord,
+) -> Option<ScatterSample> {
+    let mut choice = random::<f64>();
+    let mut layer = 0;
+    while layer < N - 1 && choice >= probabilities[layer] {
+        choice -= probabilities[layer];
+        layer += 1;
+    }
+
+    let mut sample = layers[layer].sample(ray_in, record)?;
+    if sample.delta {
+        sample.pdf *= probabilities[layer];
+        return Some(sample);
+    }
+
+    sample.pdf = mixture.pdf(ray_in, record, sample.direction);
+    if sample.pdf <= 0. {
+        return None;
+    }
+    sample.weight = mixture.eval(ray_in, record, sample.direction) / sample.pdf;
+    Some(sample)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lambertian::Lambertian, subsurface::Subsurface, texture::SolidColour};
+
+    #[test]
+    fn mixes_keep_the_interior_medium() {
+        let grey = Colour::new([0.5, 0.5, 0.5]);
+        let skin: Arc<dyn Material> = Arc::new(Subsurface::new(grey, grey, 1.4));
+        let paint: Arc<dyn Material> = Arc::new(Lambertian::new(grey));
+        let weight = Arc::new(SolidColour::constant(0.5));
+
+        let mix = MixMaterial::new(paint.clone(), skin.clone(), weight.clone());
+        assert!(mix.medium().is_some());
+        let mix = MixMaterial::new(skin, paint.clone(), weight.clone());
+        assert!(mix.medium().is_some());
+        let mix = MixMaterial::new(paint.clone(), paint, weight);
+        assert!(mix.medium().is_none());
+    }
+}
@@ -0,0 +1,43 @@
+use crate::Vector;
+
+/// An orthonormal basis, for working in coordinates local to a surface with `w` as the normal.
+#[derive(Clone, Copy, Debug)]
+pub struct Onb {
+    pub u: Vector<f64, 3>,
+    pub v: Vector<f64, 3>,
+    pub w: Vector<f64, 3>,
+}
+
+impl Onb {
+    /// A basis around `w`, with `u` and `v` in an arbitrary but consistent orientation.
+    pub fn new(w: Vector<f64, 3>) -> Self {
+        let w = w.unit();
+        let a = if w.x().abs() > 0.9 {
+            Vector::new([0., 1., 0.])
+        } else {
+            Vector::new([1., 0., 0.])
+        };
+        let v = w.cross(a).unit();
+        let u = w.cross(v);
+        Self { u, v, w }
+    }
+
+    /// A basis around `w` with `u` along `tangent`, which must be perpendicular to `w`, so that
+    /// local directions follow the surface's parameterisation.
+    pub fn from_tangent(w: Vector<f64, 3>, tangent: Vector<f64, 3>) -> Self {
+        let w = w.unit();
+        let u = tangent.unit();
+        let v = w.cross(u);
+        Self { u, v, w }
+    }
+
+    /// Converts local coordinates to a world space vector.
+    pub fn to_world(&self, local: Vector<f64, 3>) -> Vector<f64, 3> {
+        self.u * local.x() + self.v * local.y() + self.w * local.z()
+    }
+
+    /// Converts a world space vector to local coordinates.
+    pub fn to_local(&self, world: Vector<f64, 3>) -> Vector<f64, 3> {
+        Vector::new([world.dot(&self.u), world.dot(&self.v), world.dot(&self.w)])
+    }
+}
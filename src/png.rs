@@ -0,0 +1,211 @@
+use std::io::{self, Write};
+
+/// Writes 8-bit RGB pixel data, in row-major order, as a PNG. The image data is stored rather
+/// than compressed, which keeps the encoder tiny at the cost of larger files.
+pub fn write_png(mut out: impl Write, width: u32, height: u32, rgb: &[u8]) -> io::Result<()> {
+    out.write_all(&[0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'])?;
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // 8 bits per channel, truecolour, default compression and filtering, not interlaced
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+    chunk(&mut out, b"IHDR", &header)?;
+
+    // Each scanline is preceded by its filter type, which is always 0 (none) here
+    let row_size = width as usize * 3;
+    let mut raw = Vec::with_capacity((row_size + 1) * height as usize);
+    for row in rgb.chunks(row_size) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    chunk(&mut out, b"IDAT", &zlib_stored(&raw))?;
+    chunk(&mut out, b"IEND", &[])
+}
+
+fn chunk(out: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+
+    let mut crc = Crc32::new();
+    crc.update(kind);
+    crc.update(data);
+    out.write_all(&crc.finish().to_be_bytes())
+}
+
+/// Wraps `data` in a zlib stream made of uncompressed deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    const MAX_BLOCK: usize = 65535;
+
+    let mut stream = vec![0x78, 0x01];
+    let mut blocks = data.chunks(MAX_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        stream.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        stream.push(last as u8);
+        let len = block.len() as u16;
+        stream.extend_from_slice(&len.to_le_bytes());
+        stream.extend_from_slice(&(!len).to_le_bytes());
+        stream.extend_from_slice(block);
+    }
+
+    // Adler-32 checksum of the uncompressed data
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    stream.extend_from_slice(&((b << 16) | a).to_be_bytes());
+
+    stream
+}
+
+struct Crc32 {
+    table: [u32; 256],
+    value: u32,
+}
+
+impl Crc32 {
+    fn new() -> Self {
+        let mut table = [0u32; 256];
+        for (n, entry) in table.iter_mut().enumerate() {
+            let mut c = n as u32;
+            for _ in 0..8 {
+                c = if c & 1 == 1 {
+                    0xedb8_8320 ^ (c >> 1)
+                } else {
+                    c >> 1
+                };
+            }
+            *entry = c;
+        }
+        Self {
+            table,
+            value: 0xffff_ffff,
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        for byte in data {
+            self.value =
+                self.table[((self.value ^ *byte as u32) & 0xff) as usize] ^ (self.value >> 8);
+        }
+    }
+
+    fn finish(&self) -> u32 {
+        self.value ^ 0xffff_ffff
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn crc32(data: &[u8]) -> u32 {
+        let mut crc = Crc32::new();
+        crc.update(data);
+        crc.finish()
+    }
+
+    /// Undoes `zlib_stored`, checking the framing of every block along the way.
+    fn inflate_stored(stream: &[u8]) -> Vec<u8> {
+        assert_eq!(stream[..2], [0x78, 0x01]);
+        let mut data = Vec::new();
+        let mut at = 2;
+        loop {
+            let last = stream[at];
+            let len = u16::from_le_bytes([stream[at + 1], stream[at + 2]]);
+            let nlen = u16::from_le_bytes([stream[at + 3], stream[at + 4]]);
+            assert_eq!(nlen, !len);
+            at += 5;
+            data.extend_from_slice(&stream[at..at + len as usize]);
+            at += len as usize;
+            if last == 1 {
+                break;
+            }
+            assert_eq!(last, 0);
+        }
+        assert_eq!(at + 4, stream.len());
+        data
+    }
+
+    fn adler32(stream: &[u8]) -> u32 {
+        u32::from_be_bytes(stream[stream.len() - 4..].try_into().unwrap())
+    }
+
+    #[test]
+    fn crc_matches_known_values() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+    }
+
+    #[test]
+    fn crc_can_be_updated_in_pieces() {
+        let mut crc = Crc32::new();
+        crc.update(b"1234");
+        crc.update(b"56789");
+        assert_eq!(crc.finish(), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn zlib_stream_of_nothing_is_one_empty_block() {
+        assert_eq!(
+            zlib_stored(&[]),
+            [0x78, 0x01, 1, 0, 0, 0xff, 0xff, 0, 0, 0, 1]
+        );
+    }
+
+    #[test]
+    fn adler_matches_known_values() {
+        assert_eq!(adler32(&zlib_stored(b"Wikipedia")), 0x11e6_0398);
+    }
+
+    #[test]
+    fn long_data_is_split_into_blocks() {
+        let data: Vec<u8> = (0..150_000u32).map(|i| (i * 7 % 251) as u8).collect();
+        let stream = zlib_stored(&data);
+        // Three blocks, each with a 5 byte header
+        assert_eq!(stream.len(), 2 + 3 * 5 + data.len() + 4);
+        assert_eq!(inflate_stored(&stream), data);
+    }
+
+    #[test]
+    fn png_round_trips() {
+        let rgb: Vec<u8> = (0..2 * 3 * 3).collect();
+        let mut png = Vec::new();
+        write_png(&mut png, 2, 3, &rgb).unwrap();
+        assert_eq!(
+            png[..8],
+            [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n']
+        );
+
+        let mut chunks = Vec::new();
+        let mut at = 8;
+        while at < png.len() {
+            let len = u32::from_be_bytes(png[at..at + 4].try_into().unwrap()) as usize;
+            let body = &png[at + 4..at + 8 + len];
+            let crc = u32::from_be_bytes(png[at + 8 + len..at + 12 + len].try_into().unwrap());
+            assert_eq!(crc32(body), crc);
+            chunks.push((body[..4].to_vec(), body[4..].to_vec()));
+            at += 12 + len;
+        }
+
+        let kinds: Vec<&[u8]> = chunks.iter().map(|c| c.0.as_slice()).collect();
+        assert_eq!(kinds, [b"IHDR", b"IDAT", b"IEND"]);
+        assert_eq!(chunks[0].1, [0, 0, 0, 2, 0, 0, 0, 3, 8, 2, 0, 0, 0]);
+
+        // Every row is preceded by filter type 0
+        let raw = inflate_stored(&chunks[1].1);
+        let rows: Vec<&[u8]> = raw.chunks(7).collect();
+        for (row, pixels) in rows.iter().zip(rgb.chunks(6)) {
+            assert_eq!(row[0], 0);
+            assert_eq!(row[1..], *pixels);
+        }
+        assert_eq!(rows.len(), 3);
+    }
+}
@@ -0,0 +1,278 @@
+use rand::random;
+
+use crate::{
+    colour::{luminance, Colour},
+    image::Image,
+};
+
+/// An effect applied to the averaged, exposed linear image before it is tone mapped.
+pub trait PostEffect: Send + Sync {
+    /// Applies the effect to `image`, which lies within the full frame as given by
+    /// `placement`.
+    fn apply(&self, image: &mut Image, placement: Placement);
+}
+
+/// Where an image lies within the full frame, so that effects centred on the frame look the same
+/// on a cropped render as on the same region of the whole image.
+#[derive(Clone, Copy, Debug)]
+pub struct Placement {
+    /// The image's top left corner within the frame.
+    pub x: u32,
+    pub y: u32,
+    pub frame_width: u32,
+    pub frame_height: u32,
+}
+
+impl Placement {
+    pub fn new(x: u32, y: u32, frame_width: u32, frame_height: u32) -> Self {
+        Self {
+            x,
+            y,
+            frame_width,
+            frame_height,
+        }
+    }
+
+    /// The placement of an image which is the whole frame.
+    pub fn whole(image: &Image) -> Self {
+        Self::new(0, 0, image.width, image.height)
+    }
+
+    /// The frame's centre, relative to the image's top left corner.
+    fn centre(&self) -> (f64, f64) {
+        (
+            self.frame_width as f64 / 2. - self.x as f64,
+            self.frame_height as f64 / 2. - self.y as f64,
+        )
+    }
+}
+
+/// Makes highlights brighter than `threshold` glow onto their neighbours.
+#[derive(Clone, Copy, Debug)]
+pub struct Bloom {
+    pub threshold: f64,
+    pub radius: u32,
+    pub intensity: f64,
+}
+
+impl Bloom {
+    pub fn new(threshold: f64, radius: u32, intensity: f64) -> Self {
+        Self {
+            threshold,
+            radius,
+            intensity,
+        }
+    }
+}
+
+impl PostEffect for Bloom {
+    fn apply(&self, image: &mut Image, _placement: Placement) {
+        // Keep only the part of each pixel brighter than the threshold
+        let mut bright = image.clone();
+        for pixel in bright.pixels.iter_mut() {
+            let l = luminance(*pixel);
+            *pixel = if l > self.threshold {
+                *pixel * ((l - self.threshold) / l)
+            } else {
+                Colour::zero()
+            };
+        }
+
+        let blurred = gaussian_blur(&bright, self.radius);
+        for (pixel, glow) in image.pixels.iter_mut().zip(blurred.pixels) {
+            *pixel = *pixel + glow * self.intensity;
+        }
+    }
+}
+
+/// Darkens the image towards its corners, as a real lens does.
+#[derive(Clone, Copy, Debug)]
+pub struct Vignette {
+    /// How much light is lost in the corners, from 0 (none) to 1 (all of it).
+    pub strength: f64,
+}
+
+impl Vignette {
+    pub fn new(strength: f64) -> Self {
+        Self { strength }
+    }
+}
+
+impl PostEffect for Vignette {
+    fn apply(&self, image: &mut Image, placement: Placement) {
+        for j in 0..image.height {
+            for i in 0..image.width {
+                let r = normalised_radius(placement, i as f64 + 0.5, j as f64 + 0.5);
+                // Natural vignetting follows cos^4 of the angle off the optical axis
+                let falloff = 1. / (1. + r * r).powi(2);
+                let factor = 1. - self.strength * (1. - falloff) * 4. / 3.;
+                image.set(i, j, image.get(i, j) * factor.max(0.));
+            }
+        }
+    }
+}
+
+/// Splits the red and blue channels radially, like a lens with lateral colour fringing.
+#[derive(Clone, Copy, Debug)]
+pub struct ChromaticAberration {
+    /// The displacement of the red and blue channels at the corners, as a fraction of the
+    /// image's half-diagonal.
+    pub strength: f64,
+}
+
+impl ChromaticAberration {
+    pub fn new(strength: f64) -> Self {
+        Self { strength }
+    }
+}
+
+impl PostEffect for ChromaticAberration {
+    fn apply(&self, image: &mut Image, placement: Placement) {
+        let source = image.clone();
+        let (cx, cy) = placement.centre();
+
+        for j in 0..image.height {
+            for i in 0..image.width {
+                let x = i as f64 + 0.5 - cx;
+                let y = j as f64 + 0.5 - cy;
+
+                let red =
+                    source.sample(cx + x * (1. + self.strength), cy + y * (1. + self.strength));
+                let green = source.get(i, j);
+                let blue =
+                    source.sample(cx + x * (1. - self.strength), cy + y * (1. - self.strength));
+
+                image.set(i, j, Colour::new([red.x(), green.y(), blue.z()]));
+            }
+        }
+    }
+}
+
+/// Adds monochromatic noise resembling photographic film grain.
+#[derive(Clone, Copy, Debug)]
+pub struct FilmGrain {
+    pub amount: f64,
+}
+
+impl FilmGrain {
+    pub fn new(amount: f64) -> Self {
+        Self { amount }
+    }
+}
+
+impl PostEffect for FilmGrain {
+    fn apply(&self, image: &mut Image, _placement: Placement) {
+        for pixel in image.pixels.iter_mut() {
+            // Sum of uniforms is a cheap approximation to a Gaussian
+            let noise = (random::<f64>() + random::<f64>() + random::<f64>() - 1.5) / 1.5;
+            *pixel = *pixel * (1. + noise * self.amount).max(0.);
+        }
+    }
+}
+
+/// Distance of a point in the image from the frame's centre, where the frame's corners are at
+/// distance 1.
+fn normalised_radius(placement: Placement, x: f64, y: f64) -> f64 {
+    let (cx, cy) = placement.centre();
+    let half_diagonal = (placement.frame_width as f64).hypot(placement.frame_height as f64) / 2.;
+    (x - cx).hypot(y - cy) / half_diagonal
+}
+
+fn gaussian_blur(image: &Image, radius: u32) -> Image {
+    if radius == 0 {
+        return image.clone();
+    }
+
+    let sigma = radius as f64 / 3.;
+    let kernel: Vec<f64> = (-(radius as i64)..=radius as i64)
+        .map(|x| (-((x * x) as f64) / (2. * sigma * sigma)).exp())
+        .collect();
+    let total: f64 = kernel.iter().sum();
+
+    let blur = |source: &Image, horizontal: bool| {
+        let mut out = Image::new(source.width, source.height);
+        for j in 0..source.height {
+            for i in 0..source.width {
+                let mut sum = Colour::zero();
+                for (k, weight) in kernel.iter().enumerate() {
+                    let offset = k as i64 - radius as i64;
+                    let (x, y) = if horizontal {
+                        (i as i64 + offset, j as i64)
+                    } else {
+                        (i as i64, j as i64 + offset)
+                    };
+                    let x = x.clamp(0, source.width as i64 - 1) as u32;
+                    let y = y.clamp(0, source.height as i64 - 1) as u32;
+                    sum = sum + source.get(x, y) * *weight;
+                }
+                out.set(i, j, sum / total);
+            }
+        }
+        out
+    };
+
+    blur(&blur(image, true), false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn total(image: &Image) -> Colour {
+        image
+            .pixels
+            .iter()
+            .fold(Colour::zero(), |sum, pixel| sum + *pixel)
+    }
+
+    #[test]
+    fn vignettes_leave_the_centre_alone() {
+        let grey = Colour::new([0.5, 0.5, 0.5]);
+        let mut image = Image::new(9, 7);
+        image.pixels.fill(grey);
+        let placement = Placement::whole(&image);
+        Vignette::new(0.8).apply(&mut image, placement);
+
+        assert_eq!(image.get(4, 3), grey);
+        assert!(image.get(0, 0).x() < grey.x());
+    }
+
+    #[test]
+    fn cropped_vignettes_leave_the_frame_centre_alone() {
+        let grey = Colour::new([0.5, 0.5, 0.5]);
+        let mut image = Image::new(4, 4);
+        image.pixels.fill(grey);
+        // The frame's centre is the centre of its pixel (10, 9), which is the crop's pixel (0, 2)
+        Vignette::new(0.8).apply(&mut image, Placement::new(10, 7, 21, 19));
+        assert_eq!(image.get(0, 2), grey);
+        assert!(image.get(3, 0).x() < grey.x());
+    }
+
+    #[test]
+    fn bloom_spreads_highlights_without_adding_energy() {
+        let mut image = Image::new(31, 31);
+        image.pixels.fill(Colour::new([0.1, 0.1, 0.1]));
+        image.set(15, 15, Colour::new([10., 10., 10.]));
+        let before = total(&image);
+
+        let bloom = Bloom::new(1., 5, 0.5);
+        let placement = Placement::whole(&image);
+        bloom.apply(&mut image, placement);
+
+        // Only the part of the highlight above the threshold glows, scaled by the intensity
+        let glow = (10. - 1.) * bloom.intensity;
+        let after = total(&image);
+        assert!((after.x() - before.x() - glow).abs() < 1e-9 * glow);
+        assert!(image.get(15, 18).x() > 0.1);
+    }
+
+    #[test]
+    fn bloom_ignores_images_below_the_threshold() {
+        let mut image = Image::new(8, 8);
+        image.pixels.fill(Colour::new([0.5, 0.5, 0.5]));
+        let before = image.clone();
+        let placement = Placement::whole(&image);
+        Bloom::new(1., 3, 1.).apply(&mut image, placement);
+        assert_eq!(image.pixels, before.pixels);
+    }
+}
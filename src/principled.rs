@@ -0,0 +1,202 @@
+use crate::{
+    colour::{luminance, Colour},
+    dielectric::Dielectric,
+    hittable::HitRecord,
+    lambertian::Lambertian,
+    material::{Material, ScatterSample},
+    metals::Metal,
+    mix::{sample_layers, Tinted},
+    Ray, Vector,
+};
+
+/// A layered, physically based material in the style of Disney's principled BSDF, whose
+/// parameters follow glTF's metallic-roughness model. One layer is chosen at random per
+/// scattering event, in proportion to how much light it reflects.
+#[derive(Clone, Copy, Debug)]
+pub struct Principled {
+    pub base_colour: Colour,
+    /// Blends from a dielectric (0) to a metal (1) whose reflections are tinted by
+    /// `base_colour`.
+    pub metallic: f64,
+    pub roughness: f64,
+    /// Stretches specular highlights along the surface's first tangent, from 0 to 1.
+    pub anisotropy: f64,
+    /// Strength of a dielectric's head-on specular reflection, where 0.5 gives the usual 4%.
+    pub specular: f64,
+    /// Strength of a clear varnish layer on top of everything else.
+    pub clearcoat: f64,
+    pub clearcoat_roughness: f64,
+    /// Strength of a soft, cloth-like glow at grazing angles.
+    pub sheen: f64,
+    /// How far the sheen is tinted towards `base_colour`.
+    pub sheen_tint: f64,
+    /// Fraction of light a dielectric lets through rather than diffusing, tinted by
+    /// `base_colour`.
+    pub transmission: f64,
+    /// Refractive index of the transmissive layer.
+    pub ior: f64,
+    /// Light given off by the surface.
+    pub emission: Colour,
+}
+
+impl Default for Principled {
+    fn default() -> Self {
+        Self {
+            base_colour: Colour::new([0.8, 0.8, 0.8]),
+            metallic: 0.,
+            roughness: 0.5,
+            anisotropy: 0.,
+            specular: 0.5,
+            clearcoat: 0.,
+            clearcoat_roughness: 0.03,
+            sheen: 0.,
+            sheen_tint: 0.5,
+            transmission: 0.,
+            ior: 1.5,
+            emission: Colour::new([0., 0., 0.]),
+        }
+    }
+}
+
+impl Principled {
+    /// A material from glTF's core parameters, with everything else at its default.
+    pub fn new(base_colour: Colour, metallic: f64, roughness: f64) -> Self {
+        Self {
+            base_colour,
+            metallic,
+            roughness,
+            ..Default::default()
+        }
+    }
+
+    /// Calls `f` with the layers, from the top down: clearcoat, metal, transmission, specular
+    /// and diffuse.
+    fn layers<T>(&self, f: impl FnOnce([&dyn Material; 5]) -> T) -> T {
+        let white = Colour::new([1., 1., 1.]);
+        let clearcoat = Metal::rough(white, self.clearcoat_roughness, 0.);
+        let metal = Metal::rough(self.base_colour, self.roughness, self.anisotropy);
+        let glass = Dielectric {
+            roughness: self.roughness,
+            ..Dielectric::new(self.ior)
+        };
+        let glass = Tinted {
+            material: &glass,
+            tint: self.base_colour,
+        };
+        let specular = Metal::rough(white, self.roughness, self.anisotropy);
+        let tint = white + (self.tint() - white) * self.sheen_tint;
+        let diffuse = Diffuse {
+            albedo: self.base_colour,
+            sheen: tint * self.sheen,
+        };
+        f([&clearcoat, &metal, &glass, &specular, &diffuse])
+    }
+
+    /// The probability of light seen along `ray_in` interacting with each layer. Light passes
+    /// through the clearcoat and specular layers unless reflected, so their strength follows
+    /// the Fresnel equations.
+    fn probabilities(&self, ray_in: &Ray, record: &HitRecord) -> [f64; 5] {
+        let cos_theta = (-ray_in.direction().unit())
+            .dot(&record.normal)
+            .clamp(0., 1.);
+        let clearcoat = self.clearcoat.clamp(0., 1.) * Self::schlick(0.04, cos_theta);
+        let metallic = self.metallic.clamp(0., 1.);
+        let transmission = self.transmission.clamp(0., 1.);
+        let specular = Self::schlick(0.08 * self.specular.clamp(0., 1.), cos_theta);
+
+        let below_clearcoat = 1. - clearcoat;
+        let dielectric = below_clearcoat * (1. - metallic);
+        let opaque = dielectric * (1. - transmission);
+        [
+            clearcoat,
+            below_clearcoat * metallic,
+            dielectric * transmission,
+            opaque * specular,
+            opaque * (1. - specular),
+        ]
+    }
+
+    fn schlick(f0: f64, cos_theta: f64) -> f64 {
+        f0 + (1. - f0) * (1. - cos_theta).powi(5)
+    }
+
+    /// The base colour with its luminance normalised away, leaving only its hue.
+    fn tint(&self) -> Colour {
+        let luminance = luminance(self.base_colour);
+        if luminance > 0. {
+            self.base_colour / luminance
+        } else {
+            Colour::new([1., 1., 1.])
+        }
+    }
+}
+
+impl Material for Principled {
+    fn sample(&self, ray_in: &Ray, record: &HitRecord) -> Option<ScatterSample> {
+        let probabilities = self.probabilities(ray_in, record);
+        // Each layer is picked with the probability that light interacts with it
+        self.layers(|layers| sample_layers(self, layers, probabilities, ray_in, record))
+    }
+
+    fn eval(&self, ray_in: &Ray, record: &HitRecord, direction: Vector<f64, 3>) -> Colour {
+        let probabilities = self.probabilities(ray_in, record);
+        self.layers(|layers| {
+            let mut total = Colour::new([0., 0., 0.]);
+            for (material, probability) in layers.iter().zip(probabilities) {
+                total = total + material.eval(ray_in, record, direction) * probability;
+            }
+            total
+        })
+    }
+
+    fn pdf(&self, ray_in: &Ray, record: &HitRecord, direction: Vector<f64, 3>) -> f64 {
+        let probabilities = self.probabilities(ray_in, record);
+        self.layers(|layers| {
+            layers
+                .iter()
+                .zip(probabilities)
+                .map(|(material, probability)| {
+                    probability * material.pdf(ray_in, record, direction)
+                })
+                .sum()
+        })
+    }
+
+    fn emitted(&self, _ray_in: &Ray, _record: &HitRecord) -> Colour {
+        self.emission
+    }
+}
+
+/// The diffuse base of a `Principled` material, with its sheen.
+struct Diffuse {
+    albedo: Colour,
+    sheen: Colour,
+}
+
+impl Material for Diffuse {
+    fn sample(&self, ray_in: &Ray, record: &HitRecord) -> Option<ScatterSample> {
+        let mut sample = Lambertian::new(self.albedo).sample(ray_in, record)?;
+        if sample.pdf <= 0. {
+            return None;
+        }
+        sample.weight = self.eval(ray_in, record, sample.direction) / sample.pdf;
+        Some(sample)
+    }
+
+    fn eval(&self, ray_in: &Ray, record: &HitRecord, direction: Vector<f64, 3>) -> Colour {
+        let diffuse = Lambertian::new(self.albedo).eval(ray_in, record, direction);
+        let cos_theta = record.normal.dot(&direction);
+        if cos_theta <= 0. {
+            return diffuse;
+        }
+
+        // Sheen brightens towards grazing angles between the light and the half vector
+        let half = (direction - ray_in.direction().unit()).unit();
+        let cos_d = half.dot(&direction).clamp(0., 1.);
+        diffuse + self.sheen * ((1. - cos_d).powi(5) * cos_theta)
+    }
+
+    fn pdf(&self, ray_in: &Ray, record: &HitRecord, direction: Vector<f64, 3>) -> f64 {
+        Lambertian::new(self.albedo).pdf(ray_in, record, direction)
+    }
+}
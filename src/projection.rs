@@ -0,0 +1,191 @@
+use std::f64::consts::PI;
+
+use crate::{degrees_to_radians, Vector};
+
+/// How the camera maps points on the image to directions in the scene.
+#[derive(Clone, Copy, Debug, Default)]
+pub enum Projection {
+    /// A pinhole (or thin lens, with defocus) perspective camera.
+    #[default]
+    Perspective,
+    /// Parallel rays, covering the same area as the perspective view does at `focus_dist`.
+    Orthographic,
+    /// An equidistant fisheye, where the angle off-axis is proportional to the distance from the
+    /// image centre. `fov` is the angle, in degrees, covered by the image width.
+    Fisheye { fov: f64 },
+    /// A full 360° by 180° latitude-longitude panorama.
+    Equirectangular,
+    /// One eye of an omni-directional stereo (ODS) panorama: an equirectangular view where each
+    /// ray leaves from a circle of radius `eye_offset` (half the interocular distance, negative
+    /// for the left eye), turned inwards to meet the other eye's rays at `convergence`.
+    OmniStereo { eye_offset: f64, convergence: f64 },
+    /// A cylinder around the camera, covering `hfov` degrees horizontally and `vfov` vertically.
+    Cylindrical { hfov: f64 },
+}
+
+impl Projection {
+    /// Returns the direction, in the camera's `(u, v, -w)` frame, of the ray through `(s, t)`.
+    /// `s` runs from 0 to 1 left to right and `t` from 0 to 1 top to bottom.
+    ///
+    /// Perspective and orthographic views are built from the viewport instead, so return `None`.
+    pub fn direction(
+        &self,
+        s: f64,
+        t: f64,
+        aspect_ratio: f64,
+        vfov: f64,
+    ) -> Option<Vector<f64, 3>> {
+        match *self {
+            Projection::Perspective | Projection::Orthographic => None,
+            Projection::Fisheye { fov } => {
+                let x = 2. * s - 1.;
+                let y = (1. - 2. * t) / aspect_ratio;
+                let r = (x * x + y * y).sqrt();
+                let theta = r * degrees_to_radians(fov) / 2.;
+                let phi = y.atan2(x);
+                Some(Vector::new([
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    theta.cos(),
+                ]))
+            }
+            Projection::Equirectangular | Projection::OmniStereo { .. } => {
+                let longitude = (s - 0.5) * 2. * PI;
+                let latitude = (0.5 - t) * PI;
+                Some(Vector::new([
+                    latitude.cos() * longitude.sin(),
+                    latitude.sin(),
+                    latitude.cos() * longitude.cos(),
+                ]))
+            }
+            Projection::Cylindrical { hfov } => {
+                let angle = (s - 0.5) * degrees_to_radians(hfov);
+                let height = (1. - 2. * t) * (degrees_to_radians(vfov) / 2.).tan();
+                Some(Vector::new([angle.sin(), height, angle.cos()]))
+            }
+        }
+    }
+
+    /// Returns where, relative to the camera centre, the ray with `direction` through `(s, _)`
+    /// leaves from, along with its final direction. Both are in the camera's `(u, v, -w)` frame.
+    pub fn eye_offset(
+        &self,
+        s: f64,
+        direction: Vector<f64, 3>,
+    ) -> (Vector<f64, 3>, Vector<f64, 3>) {
+        let Projection::OmniStereo {
+            eye_offset,
+            convergence,
+        } = *self
+        else {
+            return (Vector::new([0., 0., 0.]), direction);
+        };
+
+        // The eye sits on the tangent to its viewing direction, in the horizontal plane
+        let longitude = (s - 0.5) * 2. * PI;
+        let offset = Vector::new([longitude.cos(), 0., -longitude.sin()]) * eye_offset;
+
+        if convergence > 0. && convergence.is_finite() {
+            (offset, direction * convergence - offset)
+        } else {
+            (offset, direction)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FORWARD: [f64; 3] = [0., 0., 1.];
+
+    fn assert_near(a: Vector<f64, 3>, b: [f64; 3]) {
+        assert!((a - Vector::new(b)).length() < 1e-9, "{a:?} is not {b:?}");
+    }
+
+    /// The angle, in degrees, of `direction` around the vertical axis from straight ahead.
+    fn longitude(direction: Vector<f64, 3>) -> f64 {
+        direction.x().atan2(direction.z()).to_degrees()
+    }
+
+    #[test]
+    fn centres_look_forward() {
+        let projections = [
+            Projection::Fisheye { fov: 180. },
+            Projection::Equirectangular,
+            Projection::Cylindrical { hfov: 120. },
+            Projection::OmniStereo {
+                eye_offset: 0.03,
+                convergence: 2.,
+            },
+        ];
+        for projection in projections {
+            let direction = projection.direction(0.5, 0.5, 1.5, 60.).unwrap();
+            assert_near(direction, FORWARD);
+        }
+    }
+
+    #[test]
+    fn fisheyes_cover_their_field_of_view_across_the_width() {
+        let fisheye = Projection::Fisheye { fov: 180. };
+        assert_near(fisheye.direction(1., 0.5, 2., 60.).unwrap(), [1., 0., 0.]);
+        assert_near(fisheye.direction(0., 0.5, 2., 60.).unwrap(), [-1., 0., 0.]);
+        // Angles off-axis are proportional to the distance from the centre
+        let direction = fisheye.direction(0.75, 0.5, 2., 60.).unwrap();
+        assert!((direction.z().acos().to_degrees() - 45.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn equirectangular_edges_are_behind_the_camera() {
+        let panorama = Projection::Equirectangular;
+        let left = panorama.direction(0., 0.5, 2., 60.).unwrap();
+        let right = panorama.direction(1., 0.5, 2., 60.).unwrap();
+        assert!((longitude(left) + 180.).abs() < 1e-9, "{}", longitude(left));
+        assert!(
+            (longitude(right) - 180.).abs() < 1e-9,
+            "{}",
+            longitude(right)
+        );
+        assert!((longitude(panorama.direction(0.75, 0.5, 2., 60.).unwrap()) - 90.).abs() < 1e-9);
+
+        assert_near(panorama.direction(0.5, 0., 2., 60.).unwrap(), [0., 1., 0.]);
+        assert_near(panorama.direction(0.5, 1., 2., 60.).unwrap(), [0., -1., 0.]);
+    }
+
+    #[test]
+    fn cylinders_cover_their_field_of_view_across_the_width() {
+        let cylinder = Projection::Cylindrical { hfov: 120. };
+        let edge = cylinder.direction(1., 0.5, 2., 60.).unwrap();
+        assert!((longitude(edge) - 60.).abs() < 1e-9);
+        // Vertically, the image matches a perspective view with the same `vfov`
+        let top = cylinder.direction(0.5, 0., 2., 60.).unwrap();
+        assert!((top.y().atan2(top.z()).to_degrees() - 30.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn omni_stereo_eyes_converge() {
+        let convergence = 2.;
+        let left = Projection::OmniStereo {
+            eye_offset: -0.03,
+            convergence,
+        };
+        let right = Projection::OmniStereo {
+            eye_offset: 0.03,
+            convergence,
+        };
+        for s in [0.1, 0.5, 0.8] {
+            let direction = left.direction(s, 0.5, 2., 60.).unwrap();
+            let (left_origin, left_direction) = left.eye_offset(s, direction);
+            let (right_origin, right_direction) = right.eye_offset(s, direction);
+
+            // Eyes sit either side of the centre, at right angles to the view
+            assert!((left_origin + right_origin).length() < 1e-12);
+            assert!(left_origin.dot(&direction).abs() < 1e-12);
+
+            // Both rays pass through the same point at the convergence distance
+            let target = direction * convergence;
+            assert!((left_origin + left_direction - target).length() < 1e-12);
+            assert!((right_origin + right_direction - target).length() < 1e-12);
+        }
+    }
+}
@@ -5,6 +5,8 @@ pub struct Ray {
     origin: Point<f64, 3>,
     direction: Vector<f64, 3>,
     time: f64,
+    /// The wavelength carried, in nanometres, when rendering spectrally.
+    wavelength: Option<f64>,
 }
 
 impl Ray {
@@ -13,9 +15,15 @@ impl Ray {
             origin,
             direction,
             time: time.unwrap_or(0.0),
+            wavelength: None,
         }
     }
 
+    /// Returns this ray carrying the given wavelength instead.
+    pub fn with_wavelength(self, wavelength: Option<f64>) -> Self {
+        Ray { wavelength, ..self }
+    }
+
     pub fn origin(&self) -> Point<f64, 3> {
         self.origin
     }
@@ -28,6 +36,10 @@ impl Ray {
         self.time
     }
 
+    pub fn wavelength(&self) -> Option<f64> {
+        self.wavelength
+    }
+
     pub fn at(&self, t: f64) -> Point<f64, 3> {
         self.origin + self.direction * t
     }
@@ -0,0 +1,230 @@
+/// A piecewise-constant 1D distribution, for drawing samples proportional to a function
+/// tabulated at `n` evenly spaced points over `[0, 1)`.
+#[derive(Clone, Debug)]
+pub struct Distribution1D {
+    func: Vec<f64>,
+    cdf: Vec<f64>,
+    integral: f64,
+}
+
+impl Distribution1D {
+    pub fn new(func: Vec<f64>) -> Self {
+        assert!(!func.is_empty(), "A distribution needs at least one value");
+        let n = func.len();
+        let mut cdf = vec![0.0; n + 1];
+        for i in 0..n {
+            cdf[i + 1] = cdf[i] + func[i].abs() / n as f64;
+        }
+        let integral = cdf[n];
+
+        if integral == 0.0 {
+            // Nothing to prefer, so fall back to sampling uniformly
+            for (i, c) in cdf.iter_mut().enumerate() {
+                *c = i as f64 / n as f64;
+            }
+        } else {
+            for c in cdf.iter_mut() {
+                *c /= integral;
+            }
+        }
+
+        Self {
+            func,
+            cdf,
+            integral,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.func.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.func.is_empty()
+    }
+
+    pub fn integral(&self) -> f64 {
+        self.integral
+    }
+
+    /// Maps a uniform `u` in `[0, 1)` to a point in `[0, 1)`, returning it along with its
+    /// density and the index of the segment it falls in.
+    pub fn sample_continuous(&self, u: f64) -> (f64, f64, usize) {
+        let index = self.sample_index(u);
+        let span = self.cdf[index + 1] - self.cdf[index];
+        let du = if span > 0.0 {
+            (u - self.cdf[index]) / span
+        } else {
+            0.0
+        };
+
+        let x = (index as f64 + du) / self.len() as f64;
+        (x, self.pdf(x), index)
+    }
+
+    /// Picks a segment with probability proportional to its value, returning it along with that
+    /// probability.
+    pub fn sample_discrete(&self, u: f64) -> (usize, f64) {
+        let index = self.sample_index(u);
+        (index, self.cdf[index + 1] - self.cdf[index])
+    }
+
+    /// The density of `sample_continuous` at `x`.
+    pub fn pdf(&self, x: f64) -> f64 {
+        let index = ((x * self.len() as f64) as usize).min(self.len() - 1);
+        if self.integral == 0.0 {
+            return 1.0;
+        }
+        self.func[index].abs() / self.integral
+    }
+
+    fn sample_index(&self, u: f64) -> usize {
+        // The last entry <= u
+        let index = self.cdf.partition_point(|c| *c <= u);
+        index.clamp(1, self.len()) - 1
+    }
+}
+
+/// A piecewise-constant 2D distribution over `[0, 1)^2`, for drawing samples proportional to a
+/// function tabulated on a `width` by `height` grid, stored in row-major order.
+#[derive(Clone, Debug)]
+pub struct Distribution2D {
+    conditional: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    pub fn new(func: &[f64], width: usize, height: usize) -> Self {
+        assert!(
+            width > 0 && height > 0 && func.len() >= width * height,
+            "A distribution needs a value for every cell of a non-empty grid"
+        );
+        let conditional: Vec<Distribution1D> = func
+            .chunks(width)
+            .take(height)
+            .map(|row| Distribution1D::new(row.to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(conditional.iter().map(|d| d.integral()).collect());
+
+        Self {
+            conditional,
+            marginal,
+        }
+    }
+
+    /// Maps two uniform values to a point `(x, y)`, returning it along with its density.
+    pub fn sample(&self, u: f64, v: f64) -> ((f64, f64), f64) {
+        let (y, pdf_y, row) = self.marginal.sample_continuous(v);
+        let (x, pdf_x, _) = self.conditional[row].sample_continuous(u);
+        ((x, y), pdf_x * pdf_y)
+    }
+
+    pub fn pdf(&self, x: f64, y: f64) -> f64 {
+        let row = ((y * self.marginal.len() as f64) as usize).min(self.marginal.len() - 1);
+        self.marginal.pdf(y) * self.conditional[row].pdf(x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-12
+    }
+
+    #[test]
+    fn samples_in_proportion_to_the_function() {
+        let distribution = Distribution1D::new(vec![1., 3.]);
+        assert!(close(distribution.integral(), 2.));
+
+        let (x, pdf, index) = distribution.sample_continuous(0.125);
+        assert!(close(x, 0.25) && close(pdf, 0.5) && index == 0);
+        let (x, pdf, index) = distribution.sample_continuous(0.625);
+        assert!(close(x, 0.75) && close(pdf, 1.5) && index == 1);
+
+        let (index, probability) = distribution.sample_discrete(0.3);
+        assert_eq!(index, 1);
+        assert!(close(probability, 0.75));
+    }
+
+    #[test]
+    fn zero_function_samples_uniformly() {
+        let distribution = Distribution1D::new(vec![0.; 4]);
+        let (x, pdf, index) = distribution.sample_continuous(0.3);
+        assert!(close(x, 0.3) && close(pdf, 1.) && index == 1);
+    }
+
+    #[test]
+    fn empty_segments_are_never_sampled() {
+        let distribution = Distribution1D::new(vec![0., 2., 0., 2.]);
+        for i in 0..100 {
+            let (x, pdf, index) = distribution.sample_continuous(i as f64 / 100.);
+            assert!(index == 1 || index == 3, "{x}");
+            assert!(close(pdf, 2.));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one value")]
+    fn empty_functions_are_rejected() {
+        Distribution1D::new(Vec::new());
+    }
+
+    #[test]
+    #[should_panic(expected = "non-empty grid")]
+    fn empty_grids_are_rejected() {
+        Distribution2D::new(&[], 0, 4);
+    }
+
+    fn grid() -> (Vec<f64>, Distribution2D) {
+        let func = vec![1., 3., 0., 0., 2., 6., 4., 8.];
+        let distribution = Distribution2D::new(&func, 2, 4);
+        (func, distribution)
+    }
+
+    #[test]
+    fn pdf_is_the_normalised_function() {
+        let (func, distribution) = grid();
+        // The function averages 3 over the unit square
+        for (k, f) in func.iter().enumerate() {
+            let (x, y) = ((k % 2) as f64 / 2. + 0.25, (k / 2) as f64 / 4. + 0.125);
+            assert!(close(distribution.pdf(x, y), f / 3.), "({x}, {y})");
+        }
+    }
+
+    #[test]
+    fn samples_report_their_own_density() {
+        let (_, distribution) = grid();
+        for i in 0..20 {
+            for j in 0..20 {
+                let (u, v) = ((i as f64 + 0.5) / 20., (j as f64 + 0.5) / 20.);
+                let ((x, y), pdf) = distribution.sample(u, v);
+                assert!((0. ..1.).contains(&x) && (0. ..1.).contains(&y));
+                assert!(pdf > 0., "({x}, {y}) sampled from an empty cell");
+                assert!(close(pdf, distribution.pdf(x, y)), "({x}, {y})");
+            }
+        }
+    }
+
+    #[test]
+    fn samples_land_in_cells_in_proportion_to_the_function() {
+        let (func, distribution) = grid();
+        let n = 200;
+        let mut counts = [0; 8];
+        for i in 0..n {
+            for j in 0..n {
+                let (u, v) = ((i as f64 + 0.5) / n as f64, (j as f64 + 0.5) / n as f64);
+                let ((x, y), _) = distribution.sample(u, v);
+                counts[(y * 4.) as usize * 2 + (x * 2.) as usize] += 1;
+            }
+        }
+        // The mapping is monotonic, so stratified samples split between the cells to within a
+        // stratum in each direction
+        let total: f64 = func.iter().sum();
+        for (count, f) in counts.iter().zip(&func) {
+            let fraction = *count as f64 / (n * n) as f64;
+            assert!((fraction - f / total).abs() < 2. / n as f64, "{fraction}");
+        }
+    }
+}
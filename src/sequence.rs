@@ -0,0 +1,127 @@
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{aov::AovOutput, camera::Camera, hittable::Hittable, shutter::Shutter};
+
+/// Renders a range of animation frames to numbered PNGs, advancing scene time with each frame.
+/// The camera's crop window applies to every frame, and if it has an AOV output, each frame's
+/// layers are written there with the frame number appended.
+pub struct Sequence {
+    /// The first and last frame numbers to render, inclusive.
+    pub first_frame: u32,
+    pub last_frame: u32,
+    /// Scene time at the start of frame 0.
+    pub start_time: f64,
+    /// Scene time between the start of one frame and the start of the next.
+    pub frame_duration: f64,
+    /// Fraction of each frame's duration the shutter is open for, e.g. 0.5 for a 180° shutter.
+    pub shutter_fraction: f64,
+    pub directory: PathBuf,
+}
+
+impl Sequence {
+    pub fn new(first_frame: u32, last_frame: u32, frame_duration: f64, directory: PathBuf) -> Self {
+        Self {
+            first_frame,
+            last_frame,
+            start_time: 0.,
+            frame_duration,
+            shutter_fraction: 0.5,
+            directory,
+        }
+    }
+
+    pub fn frame_path(&self, frame: u32) -> PathBuf {
+        self.directory.join(format!("frame_{frame:04}.png"))
+    }
+
+    /// Where the layers of `frame` go, given where those of a single image would.
+    pub fn frame_aovs(output: &AovOutput, frame: u32) -> AovOutput {
+        match output {
+            AovOutput::Files { prefix } => AovOutput::Files {
+                prefix: format!("{prefix}_{frame:04}"),
+            },
+            AovOutput::Exr { path } => {
+                let path = Path::new(path);
+                let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+                let numbered = path.with_file_name(format!("{stem}_{frame:04}.exr"));
+                AovOutput::Exr {
+                    path: numbered.to_string_lossy().into_owned(),
+                }
+            }
+        }
+    }
+
+    /// Renders every frame not already on disk. The same `world` (typically a `BvhNode`) is
+    /// used for every frame, since objects move according to ray time rather than by rebuilding.
+    pub fn render(&self, camera: &mut Camera, world: &impl Hittable) -> io::Result<()> {
+        fs::create_dir_all(&self.directory)?;
+        let curve = camera.shutter.unwrap_or_default().curve;
+
+        // For logging
+        let mut stderr = io::stderr();
+
+        for frame in self.first_frame..=self.last_frame {
+            let path = self.frame_path(frame);
+            if path.exists() {
+                let message = format!(
+                    "Skipping frame {frame}, {} already exists\n",
+                    path.display()
+                );
+                let _ = stderr.write(message.as_bytes());
+                continue;
+            }
+
+            let open = self.start_time + frame as f64 * self.frame_duration;
+            let close = open + self.frame_duration * self.shutter_fraction;
+            camera.shutter = Some(Shutter { open, close, curve });
+            camera.initialise();
+
+            let _ = stderr.write(format!("Rendering frame {frame}\n").as_bytes());
+            let region = camera.region();
+            let layers = camera.render_region(world, region.columns(), region.rows());
+            let layers = camera.placed(layers);
+
+            // Write to a temporary file first, so an interrupted render doesn't leave behind a
+            // partial frame that would be skipped next time
+            let partial = path.with_extension("png.partial");
+            camera.write_png(File::create(&partial)?, &layers);
+            // The frame's AOVs go first, so a frame on disk always has them too
+            if let Some(output) = &camera.aov_output {
+                layers
+                    .scaled(1. / camera.samples_per_pixel as f64)
+                    .write(&Self::frame_aovs(output, frame))?;
+            }
+            fs::rename(&partial, &path)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_get_their_own_aovs() {
+        let files = AovOutput::Files {
+            prefix: "out/shot".to_string(),
+        };
+        let AovOutput::Files { prefix } = Sequence::frame_aovs(&files, 7) else {
+            panic!("AOV files became an EXR");
+        };
+        assert_eq!(prefix, "out/shot_0007");
+
+        let exr = AovOutput::Exr {
+            path: "out/shot.exr".to_string(),
+        };
+        let AovOutput::Exr { path } = Sequence::frame_aovs(&exr, 12) else {
+            panic!("An EXR became AOV files");
+        };
+        assert_eq!(path, "out/shot_0012.exr");
+    }
+}
@@ -0,0 +1,132 @@
+use rand::random;
+
+/// How the shutter's transmission varies while it is open.
+#[derive(Clone, Copy, Debug, Default)]
+pub enum ShutterCurve {
+    /// Fully open for the whole interval.
+    #[default]
+    Box,
+    /// Opens linearly to full at the midpoint, then closes linearly.
+    Triangle,
+    /// Opens linearly over the first `ramp` fraction of the interval, and closes over the last.
+    Trapezoid { ramp: f64 },
+}
+
+/// The interval of scene time over which each image is exposed.
+#[derive(Clone, Copy, Debug)]
+pub struct Shutter {
+    pub open: f64,
+    pub close: f64,
+    pub curve: ShutterCurve,
+}
+
+impl Shutter {
+    pub fn new(open: f64, close: f64) -> Self {
+        Self {
+            open,
+            close,
+            curve: ShutterCurve::Box,
+        }
+    }
+
+    /// Returns a random time within the interval, weighted by the shutter curve.
+    pub fn sample(&self) -> f64 {
+        let u = random::<f64>();
+        let t = match self.curve {
+            ShutterCurve::Box => u,
+            ShutterCurve::Triangle => trapezoid(u, 0.5),
+            ShutterCurve::Trapezoid { ramp } => trapezoid(u, ramp.clamp(0., 0.5)),
+        };
+        self.open + t * (self.close - self.open)
+    }
+}
+
+impl Default for Shutter {
+    fn default() -> Self {
+        Self::new(0., 1.)
+    }
+}
+
+/// Inverts the CDF of a trapezoid over `[0, 1]` with ramps `ramp` wide at each end.
+fn trapezoid(u: f64, ramp: f64) -> f64 {
+    if ramp == 0. {
+        return u;
+    }
+
+    // Work in terms of the unnormalised area under the curve, which totals 1 - ramp
+    let area = u * (1. - ramp);
+    if area < ramp / 2. {
+        (2. * area * ramp).sqrt()
+    } else if area < 1. - 1.5 * ramp {
+        area + ramp / 2.
+    } else {
+        1. - (2. * (1. - ramp - area) * ramp).max(0.).sqrt()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The fraction of the trapezoid's area to the left of `t`, worked out geometrically.
+    fn cdf(t: f64, ramp: f64) -> f64 {
+        let area = if t < ramp {
+            t * t / (2. * ramp)
+        } else if t < 1. - ramp {
+            ramp / 2. + (t - ramp)
+        } else {
+            1. - ramp - (1. - t) * (1. - t) / (2. * ramp)
+        };
+        area / (1. - ramp)
+    }
+
+    #[test]
+    fn trapezoid_inverts_its_cdf() {
+        for ramp in [0.05, 0.25, 0.4, 0.5] {
+            for i in 0..=100 {
+                let t = i as f64 / 100.;
+                let inverted = trapezoid(cdf(t, ramp), ramp);
+                assert!(
+                    (inverted - t).abs() < 1e-9,
+                    "ramp {ramp}, t {t}: {inverted}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn trapezoid_matches_known_values() {
+        // A triangle takes a quarter of its area to reach a quarter of the way, in each half
+        assert!((trapezoid(0.125, 0.5) - 0.25).abs() < 1e-12);
+        assert!((trapezoid(0.5, 0.5) - 0.5).abs() < 1e-12);
+        assert!((trapezoid(0.875, 0.5) - 0.75).abs() < 1e-12);
+        assert_eq!(trapezoid(0., 0.25), 0.);
+        assert!((trapezoid(1., 0.25) - 1.).abs() < 1e-12);
+    }
+
+    #[test]
+    fn trapezoid_without_ramps_is_a_box() {
+        for u in [0., 0.3, 0.999] {
+            assert_eq!(trapezoid(u, 0.), u);
+        }
+    }
+
+    #[test]
+    fn samples_stay_within_the_interval() {
+        for curve in [
+            ShutterCurve::Box,
+            ShutterCurve::Triangle,
+            ShutterCurve::Trapezoid { ramp: 0.2 },
+            ShutterCurve::Trapezoid { ramp: 2. },
+        ] {
+            let shutter = Shutter {
+                curve,
+                ..Shutter::new(-0.25, 0.25)
+            };
+            for _ in 0..1000 {
+                let t = shutter.sample();
+                assert!((-0.25..=0.25).contains(&t), "{curve:?}: {t}");
+            }
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use std::sync::Arc;
+
+use crate::{
+    colour::Colour,
+    hittable::{HitRecord, SurfacePoint},
+    material::{Material, ScatterSample},
+    medium::Medium,
+    Ray, Vector,
+};
+
+/// What one-sided geometry looks like from behind.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BackFace {
+    /// Rays pass straight through back faces, as if they weren't there.
+    Invisible,
+    /// Back faces absorb all light, and give none off.
+    Black,
+}
+
+/// Makes another material one-sided, so that only the side its outward normals point from is
+/// shaded as usual.
+pub struct OneSided {
+    pub material: Arc<dyn Material>,
+    pub back: BackFace,
+}
+
+impl OneSided {
+    pub fn new(material: Arc<dyn Material>, back: BackFace) -> Self {
+        Self { material, back }
+    }
+}
+
+impl Material for OneSided {
+    fn sample(&self, ray_in: &Ray, record: &HitRecord) -> Option<ScatterSample> {
+        if !record.front_face {
+            return None;
+        }
+        self.material.sample(ray_in, record)
+    }
+
+    fn eval(&self, ray_in: &Ray, record: &HitRecord, direction: Vector<f64, 3>) -> Colour {
+        if !record.front_face {
+            return Colour::new([0., 0., 0.]);
+        }
+        self.material.eval(ray_in, record, direction)
+    }
+
+    fn pdf(&self, ray_in: &Ray, record: &HitRecord, direction: Vector<f64, 3>) -> f64 {
+        if !record.front_face {
+            return 0.;
+        }
+        self.material.pdf(ray_in, record, direction)
+    }
+
+    fn opacity(&self, surface: &SurfacePoint) -> f64 {
+        if !surface.front_face && self.back == BackFace::Invisible {
+            return 0.;
+        }
+        self.material.opacity(surface)
+    }
+
+    fn emitted(&self, ray_in: &Ray, record: &HitRecord) -> Colour {
+        if !record.front_face {
+            return Colour::new([0., 0., 0.]);
+        }
+        self.material.emitted(ray_in, record)
+    }
+
+    fn medium(&self) -> Option<Medium> {
+        self.material.medium()
+    }
+}
+
+/// Uses different materials on the front and back of a surface, as for leaves or printed
+/// paper. Each material sees the hit as it is, with the normal flipped to face the viewer on
+/// the back, so a back material shades the inside of a closed surface just as it would alone.
+pub struct TwoSided {
+    pub front: Arc<dyn Material>,
+    pub back: Arc<dyn Material>,
+}
+
+impl TwoSided {
+    pub fn new(front: Arc<dyn Material>, back: Arc<dyn Material>) -> Self {
+        Self { front, back }
+    }
+
+    /// The material for the side of the surface which was hit.
+    fn side(&self, front_face: bool) -> &dyn Material {
+        if front_face {
+            self.front.as_ref()
+        } else {
+            self.back.as_ref()
+        }
+    }
+}
+
+impl Material for TwoSided {
+    fn sample(&self, ray_in: &Ray, record: &HitRecord) -> Option<ScatterSample> {
+        self.side(record.front_face).sample(ray_in, record)
+    }
+
+    fn eval(&self, ray_in: &Ray, record: &HitRecord, direction: Vector<f64, 3>) -> Colour {
+        self.side(record.front_face).eval(ray_in, record, direction)
+    }
+
+    fn pdf(&self, ray_in: &Ray, record: &HitRecord, direction: Vector<f64, 3>) -> f64 {
+        self.side(record.front_face).pdf(ray_in, record, direction)
+    }
+
+    fn opacity(&self, surface: &SurfacePoint) -> f64 {
+        self.side(surface.front_face).opacity(surface)
+    }
+
+    fn emitted(&self, ray_in: &Ray, record: &HitRecord) -> Colour {
+        self.side(record.front_face).emitted(ray_in, record)
+    }
+
+    /// The medium inside is the front material's, or failing that the back material's.
+    fn medium(&self) -> Option<Medium> {
+        self.front.medium().or_else(|| self.back.medium())
+    }
+}
@@ -0,0 +1,254 @@
+use std::f64::consts::PI;
+
+use rand::random;
+
+use crate::{
+    background::{spherical_to_direction, Background},
+    colour::{xyz_to_linear_srgb, Colour},
+    onb::Onb,
+    Vector,
+};
+
+/// The Preetham et al. analytic daylight model: a clear sky lit by the sun, whose colour and
+/// brightness follow from the sun's position and the haziness of the air.
+pub struct Sky {
+    sun_direction: Vector<f64, 3>,
+    /// Angle of the sun down from the zenith, in radians, kept above the horizon.
+    sun_theta: f64,
+    /// Perez distribution coefficients `A` to `E`, for luminance `Y` and chromaticities `x`, `y`.
+    perez: [[f64; 5]; 3],
+    /// Sky colour straight up, as `(Y, x, y)`.
+    zenith: [f64; 3],
+    sun_colour: Colour,
+    /// Scales the sky's luminance, which the model gives in kcd/m².
+    pub sky_intensity: f64,
+    /// Irradiance, before atmospheric extinction, that the sun casts on a surface facing it.
+    pub sun_intensity: f64,
+    /// Angular radius of the sun, in degrees.
+    pub sun_radius: f64,
+    /// Tints the sky at the horizon to give a colour below it.
+    pub ground_albedo: Colour,
+}
+
+impl Sky {
+    /// A sky with the sun at `elevation` degrees above the horizon and `azimuth` degrees around
+    /// it, measured as in `direction_to_spherical`. `turbidity` runs from about 2 for a very
+    /// clear day to 10 for a hazy one.
+    pub fn new(elevation: f64, azimuth: f64, turbidity: f64) -> Self {
+        let t = turbidity;
+        let sun_theta = (90. - elevation).to_radians();
+        let sun_direction = spherical_to_direction(azimuth.to_radians(), sun_theta);
+        let theta = sun_theta.min(PI / 2.);
+
+        let perez = [
+            [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+            [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+        ];
+
+        let chi = (4. / 9. - t / 120.) * (PI - 2. * theta);
+        let zenith_luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let chromaticity = |c: [[f64; 4]; 3]| {
+            let cubic =
+                |k: [f64; 4]| k[0] * theta.powi(3) + k[1] * theta.powi(2) + k[2] * theta + k[3];
+            t * t * cubic(c[0]) + t * cubic(c[1]) + cubic(c[2])
+        };
+        let zenith_x = chromaticity([
+            [0.00166, -0.00375, 0.00209, 0.],
+            [-0.02903, 0.06377, -0.03202, 0.00394],
+            [0.11693, -0.21196, 0.06052, 0.25886],
+        ]);
+        let zenith_y = chromaticity([
+            [0.00275, -0.00610, 0.00317, 0.],
+            [-0.04214, 0.08970, -0.04153, 0.00516],
+            [0.15346, -0.26756, 0.06670, 0.26688],
+        ]);
+
+        Self {
+            sun_direction,
+            sun_theta,
+            perez,
+            zenith: [zenith_luminance, zenith_x, zenith_y],
+            sun_colour: Self::sun_transmittance(sun_theta, t),
+            sky_intensity: 0.05,
+            sun_intensity: 5.,
+            sun_radius: 0.27,
+            ground_albedo: Colour::new([0.3, 0.3, 0.3]),
+        }
+    }
+
+    /// The fraction of sunlight in each of red, green and blue which makes it through the
+    /// atmosphere, from Rayleigh scattering and Ångström's formula for aerosols.
+    fn sun_transmittance(sun_theta: f64, turbidity: f64) -> Colour {
+        if sun_theta >= PI / 2. {
+            return Colour::zero();
+        }
+        // Relative optical air mass, after Kasten
+        let degrees = sun_theta.to_degrees();
+        let air_mass = 1. / (sun_theta.cos() + 0.15 * (93.885 - degrees).powf(-1.253));
+        let beta = 0.04608 * turbidity - 0.04586;
+
+        let transmittance = |wavelength: f64| {
+            let rayleigh = 0.008735 * wavelength.powf(-4.08);
+            let aerosol = beta * wavelength.powf(-1.3);
+            (-(rayleigh + aerosol) * air_mass).exp()
+        };
+        // Representative wavelengths, in micrometres
+        Colour::new([
+            transmittance(0.65),
+            transmittance(0.55),
+            transmittance(0.45),
+        ])
+    }
+
+    fn perez(coefficients: &[f64; 5], cos_theta: f64, gamma: f64) -> f64 {
+        let [a, b, c, d, e] = *coefficients;
+        (1. + a * (b / cos_theta).exp()) * (1. + c * (d * gamma).exp() + e * gamma.cos().powi(2))
+    }
+
+    /// The sky's colour in the given unit direction, ignoring the sun's disk.
+    fn sky_colour(&self, direction: Vector<f64, 3>) -> Colour {
+        // The model only covers the upper hemisphere
+        let cos_theta = direction.y().max(0.01);
+        let cos_gamma = direction.dot(&self.sun_direction).clamp(-1., 1.);
+        let gamma = cos_gamma.acos();
+        let sun_theta = self.sun_theta.min(PI / 2.);
+
+        let [luminance, x, y] = [0, 1, 2].map(|i| {
+            self.zenith[i] * Self::perez(&self.perez[i], cos_theta, gamma)
+                / Self::perez(&self.perez[i], 1., sun_theta)
+        });
+
+        let xyz = Vector::new([x / y * luminance, luminance, (1. - x - y) / y * luminance]);
+        let rgb = xyz_to_linear_srgb(xyz) * self.sky_intensity;
+        Colour::new([rgb.x().max(0.), rgb.y().max(0.), rgb.z().max(0.)])
+    }
+
+    fn cos_sun_radius(&self) -> f64 {
+        self.sun_radius.to_radians().cos()
+    }
+
+    /// The radiance of the sun's disk.
+    fn sun_radiance(&self) -> Colour {
+        let solid_angle = 2. * PI * (1. - self.cos_sun_radius());
+        self.sun_colour * (self.sun_intensity / solid_angle)
+    }
+
+    fn sun_visible(&self) -> bool {
+        self.sun_theta < PI / 2.
+    }
+}
+
+impl Background for Sky {
+    fn colour(&self, direction: Vector<f64, 3>) -> Colour {
+        let direction = direction.unit();
+        if direction.y() < 0. {
+            let horizon = Vector::new([direction.x(), 0., direction.z()]);
+            return self.sky_colour(horizon).hadamard(self.ground_albedo);
+        }
+
+        let sky = self.sky_colour(direction);
+        if self.sun_visible() && direction.dot(&self.sun_direction) >= self.cos_sun_radius() {
+            return sky + self.sun_radiance();
+        }
+        sky
+    }
+
+    /// Aims half of the samples at the sun, and spreads the rest over the sky.
+    fn sample_direction(&self) -> Option<Vector<f64, 3>> {
+        if self.sun_visible() && random::<f64>() < 0.5 {
+            // Uniformly within the cone of the sun's disk
+            let cos_theta = 1. - random::<f64>() * (1. - self.cos_sun_radius());
+            let sin_theta = (1. - cos_theta * cos_theta).sqrt();
+            let phi = 2. * PI * random::<f64>();
+
+            let local = Vector::new([sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta]);
+            return Some(Onb::new(self.sun_direction).to_world(local));
+        }
+
+        // Uniformly over the upper hemisphere
+        let cos_theta = random::<f64>();
+        let phi = 2. * PI * random::<f64>();
+        Some(spherical_to_direction(phi, cos_theta.acos()))
+    }
+
+    fn pdf(&self, direction: Vector<f64, 3>) -> f64 {
+        let direction = direction.unit();
+        let hemisphere = if direction.y() > 0. {
+            1. / (2. * PI)
+        } else {
+            0.
+        };
+        if !self.sun_visible() {
+            return hemisphere;
+        }
+
+        let cone = if direction.dot(&self.sun_direction) >= self.cos_sun_radius() {
+            1. / (2. * PI * (1. - self.cos_sun_radius()))
+        } else {
+            0.
+        };
+        0.5 * cone + 0.5 * hemisphere
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colour::luminance;
+
+    #[test]
+    fn zenith_luminance_follows_preetham() {
+        // Turbidity 3 with the sun 30° up, so 60° from the zenith
+        let sky = Sky::new(30., 0., 3.);
+        let chi = (4. / 9. - 3. / 120.) * (PI - 2. * PI / 3.);
+        let expected = (4.0453 * 3. - 4.9710) * chi.tan() - 0.2155 * 3. + 2.4192;
+        assert!((sky.zenith[0] - expected).abs() < 1e-12);
+        assert!((sky.zenith[0] - 5.139156).abs() < 1e-6);
+
+        // Straight up, the Perez distribution gives exactly the zenith luminance
+        let up = sky.colour(Vector::new([0., 1., 0.]));
+        let zenith = luminance(up) / sky.sky_intensity;
+        assert!((zenith - expected).abs() < 1e-3 * expected, "{zenith}");
+    }
+
+    #[test]
+    fn radiance_stays_finite_at_the_horizon() {
+        for elevation in [-10., 0., 0.5, 30., 89.] {
+            for turbidity in [2., 5., 10.] {
+                let sky = Sky::new(elevation, 40., turbidity);
+                for y in [0., 1e-9, 1e-3, -1e-9, -0.5] {
+                    for azimuth in [0f64, 40., 90., 180.] {
+                        let azimuth = azimuth.to_radians();
+                        let direction = Vector::new([azimuth.sin(), y, azimuth.cos()]);
+                        let colour = sky.colour(direction);
+                        for c in [colour.x(), colour.y(), colour.z()] {
+                            assert!(
+                                c.is_finite() && c >= 0.,
+                                "{elevation}° T{turbidity} y={y}: {colour:?}"
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use std::sync::OnceLock;
+
+use rand::random;
+
+use crate::{
+    colour::{xyz_to_linear_srgb, Colour},
+    Vector,
+};
+
+/// The shortest wavelength traced in spectral mode, in nanometres.
+pub const MIN_WAVELENGTH: f64 = 380.;
+/// The longest wavelength traced in spectral mode, in nanometres.
+pub const MAX_WAVELENGTH: f64 = 780.;
+
+/// The CIE 1931 2° colour matching functions at `wavelength` nanometres, using the multi-lobe
+/// Gaussian fit of Wyman, Sloan and Shirley, "Simple Analytic Approximations to the CIE XYZ
+/// Color Matching Functions" (2013).
+pub fn cie_xyz(wavelength: f64) -> Vector<f64, 3> {
+    let g = |mu: f64, sigma_below: f64, sigma_above: f64| {
+        let sigma = if wavelength < mu {
+            sigma_below
+        } else {
+            sigma_above
+        };
+        let t = (wavelength - mu) / sigma;
+        (-0.5 * t * t).exp()
+    };
+
+    Vector::new([
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    ])
+}
+
+/// Picks a wavelength, in nanometres, uniformly over the visible range.
+pub fn sample_wavelength() -> f64 {
+    MIN_WAVELENGTH + random::<f64>() * (MAX_WAVELENGTH - MIN_WAVELENGTH)
+}
+
+/// The weight by which to multiply the RGB radiance carried at a wavelength from
+/// `sample_wavelength`. It is the linear sRGB colour of that wavelength, normalised so that it
+/// averages to white over all wavelengths, so scenes without dispersion render as they would in
+/// RGB.
+pub fn wavelength_weight(wavelength: f64) -> Colour {
+    static AVERAGE: OnceLock<Colour> = OnceLock::new();
+    let average = AVERAGE.get_or_init(|| {
+        const STEPS: u32 = 1000;
+        let mut sum = Colour::new([0., 0., 0.]);
+        for step in 0..STEPS {
+            let t = (step as f64 + 0.5) / STEPS as f64;
+            let wavelength = MIN_WAVELENGTH + t * (MAX_WAVELENGTH - MIN_WAVELENGTH);
+            sum = sum + xyz_to_linear_srgb(cie_xyz(wavelength));
+        }
+        sum / STEPS as f64
+    });
+
+    let rgb = xyz_to_linear_srgb(cie_xyz(wavelength));
+    Colour::new([
+        rgb.x() / average.x(),
+        rgb.y() / average.y(),
+        rgb.z() / average.z(),
+    ])
+}
+
+/// How a material's refractive index varies with wavelength.
+#[derive(Clone, Copy, Debug)]
+pub enum Dispersion {
+    /// Cauchy's equation, `n = a + b / λ²` with `λ` in micrometres.
+    Cauchy { a: f64, b: f64 },
+    /// The Sellmeier equation, `n² = 1 + Σ bᵢλ² / (λ² - cᵢ)` with `λ` in micrometres.
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+impl Dispersion {
+    /// Schott N-BK7, a common crown glass.
+    pub const BK7: Dispersion = Dispersion::Sellmeier {
+        b: [1.039_612_12, 0.231_792_344, 1.010_469_45],
+        c: [0.006_000_698_67, 0.020_017_914_4, 103.560_653],
+    };
+
+    /// Schott SF11, a dense flint glass with strong dispersion, as used in prisms.
+    pub const SF11: Dispersion = Dispersion::Sellmeier {
+        b: [1.737_596_95, 0.313_747_346, 1.898_781_01],
+        c: [0.013_188_707, 0.062_306_814_2, 155.236_29],
+    };
+
+    pub const DIAMOND: Dispersion = Dispersion::Sellmeier {
+        b: [0.3306, 4.3356, 0.],
+        c: [0.030_625, 0.011_236, 0.],
+    };
+
+    /// The refractive index at `wavelength` nanometres.
+    pub fn refractive_index(&self, wavelength: f64) -> f64 {
+        let micrometres = wavelength / 1000.;
+        let l2 = micrometres * micrometres;
+        match *self {
+            Dispersion::Cauchy { a, b } => a + b / l2,
+            Dispersion::Sellmeier { b, c } => {
+                let sum: f64 = (0..3).map(|i| b[i] * l2 / (l2 - c[i])).sum();
+                (1. + sum).sqrt()
+            }
+        }
+    }
+}
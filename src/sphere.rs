@@ -1,7 +1,8 @@
-use std::sync::Arc;
+use std::{f64::consts::PI, sync::Arc};
 
 use crate::{
-    hittable::{self, Hittable},
+    aabb::Aabb,
+    hittable::{self, Hittable, SurfacePoint},
     lambertian::Lambertian,
     material::Material,
     Interval, Ray,
@@ -13,11 +14,9 @@ pub struct Sphere {
     centre: Ray,
     radius: f64,
     material: Arc<dyn Material>,
+    material_id: u32,
 }
 
-unsafe impl Send for Sphere {}
-unsafe impl Sync for Sphere {}
-
 impl Sphere {
     pub fn new(
         centre: Point<f64, 3>,
@@ -30,15 +29,44 @@ impl Sphere {
                 centre: Ray::new(centre, c2 - centre, None),
                 radius,
                 material,
+                material_id: 0,
             }
         } else {
             Self {
                 centre: Ray::new(centre, Vector::new([0., 0., 0.]), None),
                 radius,
                 material,
+                material_id: 0,
             }
         }
     }
+
+    /// Sets the ID written to the material ID AOV, e.g. from a scene's `MaterialIds`.
+    pub fn with_material_id(mut self, material_id: u32) -> Self {
+        self.material_id = material_id;
+        self
+    }
+
+    /// Maps a point on the unit sphere to texture coordinates in `[0, 1]`, with `u` running
+    /// around from -x and `v` from the bottom to the top.
+    fn uv(p: &Vector<f64, 3>) -> (f64, f64) {
+        let theta = (-p.y()).clamp(-1., 1.).acos();
+        let phi = (-p.z()).atan2(p.x()) + PI;
+        (phi / (2. * PI), theta / PI)
+    }
+
+    /// The derivatives of a point on the sphere with respect to `u` and `v`, from its unit
+    /// normal `n`.
+    fn tangents(n: &Vector<f64, 3>, radius: f64) -> (Vector<f64, 3>, Vector<f64, 3>) {
+        let dpdu = Vector::new([n.z(), 0., -n.x()]) * (2. * PI * radius);
+        let sin_theta = (1. - n.y() * n.y()).max(1e-8).sqrt();
+        let dpdv = Vector::new([
+            -n.x() * n.y() / sin_theta,
+            sin_theta,
+            -n.z() * n.y() / sin_theta,
+        ]) * (PI * radius);
+        (dpdu, dpdv)
+    }
 }
 
 impl Default for Sphere {
@@ -47,6 +75,7 @@ impl Default for Sphere {
             centre: Ray::default(),
             radius: 0.0,
             material: Arc::new(Lambertian::default()),
+            material_id: 0,
         }
     }
 }
@@ -58,7 +87,9 @@ impl Hittable for Sphere {
         ray_t: Interval,
         record: &mut hittable::HitRecord,
     ) -> bool {
-        let current_centre = self.centre.at(ray.time());
+        // The sphere moves from its first centre at time 0 to its second at time 1, and holds
+        // still outside that interval, so that it stays within its bounding box
+        let current_centre = self.centre.at(ray.time().clamp(0., 1.));
         let oc = ray.origin() - current_centre;
         let a = ray.direction().length_squared();
         let half_b = oc.dot(&ray.direction());
@@ -70,34 +101,88 @@ impl Hittable for Sphere {
         }
         let sqrt_disc = discriminant.sqrt();
 
-        let mut root = (-half_b - sqrt_disc) / a;
-
-        if !ray_t.surrounds(root) {
-            root = (-half_b + sqrt_disc) / a;
+        // Try the nearer root first, falling back to the further one if it is out of range or
+        // cut away by an alpha mask
+        for root in [(-half_b - sqrt_disc) / a, (-half_b + sqrt_disc) / a] {
             if !ray_t.surrounds(root) {
-                return false;
+                continue;
             }
+
+            let p = ray.at(root);
+            let outward_normal = (p - current_centre) / self.radius;
+            let (u, v) = Self::uv(&outward_normal);
+            let surface = SurfacePoint {
+                p,
+                u,
+                v,
+                time: ray.time(),
+                front_face: ray.direction().dot(&outward_normal) < 0.,
+            };
+            if !surface.is_opaque(self.material.as_ref()) {
+                continue;
+            }
+
+            record.distance = root;
+            record.p = p;
+            record.set_face_normal(ray, &outward_normal);
+            (record.u, record.v) = (u, v);
+            record.time = surface.time;
+            (record.dpdu, record.dpdv) = Self::tangents(&outward_normal, self.radius);
+            record.material = self.material.clone();
+            record.material_id = self.material_id;
+            return true;
         }
 
-        record.distance = root;
-        record.p = ray.at(record.distance);
-        let outward_normal = (record.p - current_centre) / self.radius;
-        record.set_face_normal(ray, &outward_normal);
-        record.material = self.material.clone();
+        false
+    }
 
-        true
+    fn bounding_box(&self) -> Aabb {
+        let r = Vector::new([self.radius, self.radius, self.radius]);
+        let start = self.centre.at(0.);
+        let end = self.centre.at(1.);
+        Aabb::union(
+            Aabb::from_points(start - r, start + r),
+            Aabb::from_points(end - r, end + r),
+        )
     }
 }
 
 impl Material for Sphere {
-    fn scatter(
+    fn sample(
         &self,
         ray_in: &crate::Ray,
         record: &hittable::HitRecord,
-        attenuation: &mut crate::colour::Colour,
-        scattered: &mut crate::Ray,
-    ) -> bool {
-        self.material
-            .scatter(ray_in, record, attenuation, scattered)
+    ) -> Option<crate::material::ScatterSample> {
+        self.material.sample(ray_in, record)
+    }
+
+    fn eval(
+        &self,
+        ray_in: &crate::Ray,
+        record: &hittable::HitRecord,
+        direction: Vector<f64, 3>,
+    ) -> crate::colour::Colour {
+        self.material.eval(ray_in, record, direction)
+    }
+
+    fn pdf(
+        &self,
+        ray_in: &crate::Ray,
+        record: &hittable::HitRecord,
+        direction: Vector<f64, 3>,
+    ) -> f64 {
+        self.material.pdf(ray_in, record, direction)
+    }
+
+    fn opacity(&self, surface: &hittable::SurfacePoint) -> f64 {
+        self.material.opacity(surface)
+    }
+
+    fn emitted(&self, ray_in: &crate::Ray, record: &hittable::HitRecord) -> crate::colour::Colour {
+        self.material.emitted(ray_in, record)
+    }
+
+    fn medium(&self) -> Option<crate::medium::Medium> {
+        self.material.medium()
     }
 }
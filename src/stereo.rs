@@ -0,0 +1,110 @@
+use std::io::Write;
+
+use linalg::Point;
+
+use crate::{
+    aov::Layers, camera::Camera, hittable::Hittable, image::Image, projection::Projection,
+};
+
+/// How the two eyes' images are arranged in the output.
+#[derive(Clone, Copy, Debug, Default)]
+pub enum StereoLayout {
+    /// Left eye on the left, right eye on the right.
+    #[default]
+    SideBySide,
+    /// Left eye on top, right eye below.
+    TopBottom,
+}
+
+/// A pair of cameras, `interocular` apart, rendered into one image for stereo viewing.
+///
+/// Perspective eyes stay parallel and use lens shift to meet at `convergence`, which avoids the
+/// vertical parallax of toed-in cameras. Equirectangular cameras become omni-directional stereo.
+#[derive(Clone, Copy, Debug)]
+pub struct StereoRig {
+    pub interocular: f64,
+    /// Distance to the plane with zero parallax, where objects appear at screen depth.
+    pub convergence: f64,
+    pub layout: StereoLayout,
+}
+
+impl StereoRig {
+    pub fn new(interocular: f64, convergence: f64, layout: StereoLayout) -> Self {
+        Self {
+            interocular,
+            convergence,
+            layout,
+        }
+    }
+
+    /// Returns the camera for one eye, where `side` is -1 for the left and 1 for the right.
+    pub fn eye(&self, camera: &Camera, side: f64) -> Camera {
+        let mut eye = camera.clone();
+        let offset = side * self.interocular / 2.;
+
+        match camera.projection {
+            Projection::Equirectangular | Projection::OmniStereo { .. } => {
+                eye.projection = Projection::OmniStereo {
+                    eye_offset: offset,
+                    convergence: self.convergence,
+                };
+            }
+            _ => {
+                // Move the eye sideways, in every keyframe too if the camera is animated
+                let sideways = |lookfrom: Point<f64, 3>, lookat: Point<f64, 3>| {
+                    let w = (lookfrom - lookat).unit();
+                    camera.vup.cross(w).unit() * offset
+                };
+                let shift = sideways(camera.lookfrom, camera.lookat);
+                eye.lookfrom = camera.lookfrom + shift;
+                eye.lookat = camera.lookat + shift;
+                for keyframe in eye.keyframes.iter_mut() {
+                    let shift = sideways(keyframe.lookfrom, keyframe.lookat);
+                    keyframe.lookfrom = keyframe.lookfrom + shift;
+                    keyframe.lookat = keyframe.lookat + shift;
+                }
+
+                if let Projection::Perspective = camera.projection {
+                    // Shift the view so a point `convergence` ahead of the rig's centre lands in
+                    // the middle of both eyes' images
+                    eye.initialise();
+                    let aspect_ratio = eye.width as f64 / eye.height() as f64;
+                    let half_width = (eye.vfov.to_radians() / 2.).tan() * aspect_ratio;
+                    eye.shift_x = camera.shift_x - offset / (2. * half_width * self.convergence);
+                }
+            }
+        }
+
+        eye
+    }
+
+    /// Renders both eyes and writes them, side by side or stacked, to `file` as a PNG. Their
+    /// layers, arranged the same way, go to the camera's AOV output if it has one.
+    pub fn render(&self, camera: &Camera, file: impl Write, world: &impl Hittable) {
+        let render_eye = |side: f64| {
+            let mut eye = self.eye(camera, side);
+            eye.initialise();
+            let layers = eye.render_rows(world, 0..eye.height());
+            (layers, eye)
+        };
+
+        let (left, eye) = render_eye(-1.);
+        let (right, right_eye) = render_eye(1.);
+
+        // Each eye is developed on its own, so effects centred on the frame stay centred
+        let (width, height) = (left.beauty.width, left.beauty.height);
+        let (x, y) = match self.layout {
+            StereoLayout::SideBySide => (width, 0),
+            StereoLayout::TopBottom => (0, height),
+        };
+        let mut image = Image::new(width + x, height + y);
+        image.blit(0, 0, &eye.develop(&left));
+        image.blit(x, y, &right_eye.develop(&right));
+        eye.write_png_image(file, &image);
+
+        let mut layers = Layers::new(width + x, height + y);
+        layers.blit(0, 0, &left);
+        layers.blit(x, y, &right);
+        eye.write_aovs(&layers);
+    }
+}
@@ -0,0 +1,64 @@
+use crate::{
+    colour::Colour,
+    dielectric::Dielectric,
+    hittable::HitRecord,
+    material::{Material, ScatterSample},
+    medium::Medium,
+    Ray, Vector,
+};
+
+/// A translucent material like skin, wax or marble. Light refracts in through a dielectric
+/// boundary and takes a random walk through the medium inside before leaving the surface
+/// elsewhere, so it is only meaningful on closed surfaces.
+pub struct Subsurface {
+    pub boundary: Dielectric,
+    pub medium: Medium,
+}
+
+impl Subsurface {
+    /// A material which appears as `albedo` once light has scattered many times beneath its
+    /// surface. `mean_free_path` is how far each of red, green and blue travel on average
+    /// between scattering events, which sets how far light bleeds below the surface.
+    pub fn new(albedo: Colour, mean_free_path: Colour, refractive_index: f64) -> Self {
+        let extinction = Colour::new([
+            1. / mean_free_path.x().max(1e-6),
+            1. / mean_free_path.y().max(1e-6),
+            1. / mean_free_path.z().max(1e-6),
+        ]);
+        let albedo = Colour::new([
+            Self::single_scattering_albedo(albedo.x()),
+            Self::single_scattering_albedo(albedo.y()),
+            Self::single_scattering_albedo(albedo.z()),
+        ]);
+        Self {
+            boundary: Dielectric::new(refractive_index),
+            medium: Medium::new(extinction, albedo, 0.),
+        }
+    }
+
+    /// Inverts the albedo seen after multiple scattering in a semi-infinite medium to the
+    /// fraction of light kept at each scattering event, following van de Hulst's fit.
+    fn single_scattering_albedo(albedo: f64) -> f64 {
+        let a = albedo.clamp(0., 0.999);
+        let s = 4.097_12 + 4.208_63 * a - (9.592_17 + 41.680_8 * a + 17.712_6 * a * a).sqrt();
+        1. - s * s
+    }
+}
+
+impl Material for Subsurface {
+    fn sample(&self, ray_in: &Ray, record: &HitRecord) -> Option<ScatterSample> {
+        self.boundary.sample(ray_in, record)
+    }
+
+    fn eval(&self, ray_in: &Ray, record: &HitRecord, direction: Vector<f64, 3>) -> Colour {
+        self.boundary.eval(ray_in, record, direction)
+    }
+
+    fn pdf(&self, ray_in: &Ray, record: &HitRecord, direction: Vector<f64, 3>) -> f64 {
+        self.boundary.pdf(ray_in, record, direction)
+    }
+
+    fn medium(&self) -> Option<Medium> {
+        Some(self.medium)
+    }
+}
@@ -0,0 +1,82 @@
+use std::sync::Arc;
+
+use linalg::Point;
+
+use crate::{colour::Colour, image::Image};
+
+/// A colour which varies over a surface.
+pub trait Texture: Send + Sync {
+    /// The colour at surface coordinates `(u, v)` and point `p`.
+    fn value(&self, u: f64, v: f64, p: Point<f64, 3>) -> Colour;
+}
+
+/// The same colour everywhere.
+#[derive(Clone, Copy, Debug)]
+pub struct SolidColour {
+    pub colour: Colour,
+}
+
+impl SolidColour {
+    pub fn new(colour: Colour) -> Self {
+        Self { colour }
+    }
+
+    /// A grey whose channels are all `value`, e.g. for a constant mix weight.
+    pub fn constant(value: f64) -> Self {
+        Self::new(Colour::new([value, value, value]))
+    }
+}
+
+impl Texture for SolidColour {
+    fn value(&self, _u: f64, _v: f64, _p: Point<f64, 3>) -> Colour {
+        self.colour
+    }
+}
+
+/// A 3D checkerboard of cubes `scale` units wide, alternating between two textures.
+pub struct Checker {
+    pub scale: f64,
+    pub even: Arc<dyn Texture>,
+    pub odd: Arc<dyn Texture>,
+}
+
+impl Checker {
+    pub fn new(scale: f64, even: Arc<dyn Texture>, odd: Arc<dyn Texture>) -> Self {
+        Self { scale, even, odd }
+    }
+}
+
+impl Texture for Checker {
+    fn value(&self, u: f64, v: f64, p: Point<f64, 3>) -> Colour {
+        let cell = |x: f64| (x / self.scale).floor() as i64;
+        if (cell(p.x()) + cell(p.y()) + cell(p.z())) % 2 == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+/// An image wrapped over a surface by its texture coordinates, repeating outside `[0, 1]`.
+pub struct ImageTexture {
+    pub image: Image,
+}
+
+impl ImageTexture {
+    pub fn new(image: Image) -> Self {
+        Self { image }
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: Point<f64, 3>) -> Colour {
+        if self.image.pixels.is_empty() {
+            // Make missing images obvious
+            return Colour::new([1., 0., 1.]);
+        }
+        // Images are stored top row first, but v runs upwards
+        let x = u.rem_euclid(1.) * self.image.width as f64;
+        let y = (1. - v.rem_euclid(1.)) * self.image.height as f64;
+        self.image.sample(x, y)
+    }
+}
@@ -0,0 +1,80 @@
+use crate::colour::Colour;
+
+/// Operators for compressing linear HDR colour into the displayable `[0, 1]` range.
+#[derive(Default, Clone, Copy, Debug)]
+pub enum ToneMap {
+    /// Leaves colours untouched, so anything brighter than white is clipped.
+    #[default]
+    Clamp,
+    Reinhard,
+    /// Reinhard, but mapping `white` (and anything brighter) to pure white. `white` is kept
+    /// above a small positive minimum.
+    ExtendedReinhard {
+        white: f64,
+    },
+    /// Krzysztof Narkowicz's fit of the ACES filmic curve.
+    Aces,
+    /// John Hable's filmic curve from Uncharted 2.
+    Hable,
+}
+
+/// The dimmest `white` point `ExtendedReinhard` will use, so it never divides by zero.
+const MIN_WHITE: f64 = 1e-3;
+
+impl ToneMap {
+    pub fn apply(&self, colour: Colour) -> Colour {
+        let map = |c: f64| match *self {
+            ToneMap::Clamp => c,
+            ToneMap::Reinhard => c / (1. + c),
+            ToneMap::ExtendedReinhard { white } => {
+                let white = white.max(MIN_WHITE);
+                c * (1. + c / (white * white)) / (1. + c)
+            }
+            ToneMap::Aces => (c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14),
+            ToneMap::Hable => {
+                const EXPOSURE_BIAS: f64 = 2.0;
+                const WHITE: f64 = 11.2;
+                hable_partial(c * EXPOSURE_BIAS) / hable_partial(WHITE)
+            }
+        };
+
+        Colour::new([
+            map(colour.x().max(0.)),
+            map(colour.y().max(0.)),
+            map(colour.z().max(0.)),
+        ])
+    }
+}
+
+fn hable_partial(x: f64) -> f64 {
+    const A: f64 = 0.15; // Shoulder strength
+    const B: f64 = 0.50; // Linear strength
+    const C: f64 = 0.10; // Linear angle
+    const D: f64 = 0.20; // Toe strength
+    const E: f64 = 0.02; // Toe numerator
+    const F: f64 = 0.30; // Toe denominator
+
+    ((x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F)) - E / F
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extended_reinhard_maps_white_to_white() {
+        let tone_map = ToneMap::ExtendedReinhard { white: 4. };
+        let white = tone_map.apply(Colour::new([4., 4., 4.]));
+        assert!((white.x() - 1.).abs() < 1e-12);
+    }
+
+    #[test]
+    fn extended_reinhard_stays_finite_without_a_white_point() {
+        for white in [0., -1.] {
+            let tone_map = ToneMap::ExtendedReinhard { white };
+            for c in [0., 0.5, 10.] {
+                assert!(tone_map.apply(Colour::new([c, c, c])).x().is_finite());
+            }
+        }
+    }
+}